@@ -0,0 +1,559 @@
+//! Changes to the preferences and to the participants of an instance that has already been (partially) solved.
+//!
+//! The state of the algorithm is the result of a sequence of proposals, and the final matching
+//! does not depend on their order. When some preferences change, only the proposals that could depend
+//! on the change are cancelled: the men who made them propose again from there,
+//! and the other proposals are kept as they are.
+
+use crate::{
+    check_preferences, make_partial_rank_matrix, GaleShapley, Man, MatchingChanges,
+    PreferenceError, Side, UpdateError, Woman, UNACCEPTABLE,
+};
+
+impl GaleShapley {
+    /// Replaces the preference list of one agent of the given side, as it would be given to [`init`](Self::init),
+    /// and solves the problem again, reusing the current engagements and proposals that are still valid.
+    /// Returns the couples that changed since the last stable matching.
+    ///
+    /// Returns an error, and changes nothing, if the agent does not exist or if the list is invalid.
+    pub fn update_preferences(
+        &mut self,
+        side: Side,
+        agent: usize,
+        preferences: Vec<usize>,
+    ) -> Result<MatchingChanges, UpdateError> {
+        self.check_agent(side, agent)?;
+        self.check_new_preferences(side, agent, &preferences)?;
+        let old = self.current_matching();
+        if side == self.proposing {
+            let unchanged = self.men_preferences[agent]
+                .iter()
+                .zip(&preferences)
+                .take_while(|(w1, w2)| w1 == w2)
+                .count();
+            self.rewind([(agent, unchanged)]);
+            self.men_preferences[agent] = preferences;
+        } else {
+            let ranks =
+                make_partial_rank_matrix(vec![preferences], self.men_preferences.len()).remove(0);
+            self.rewind(self.outdated_proposals_to(agent, &ranks));
+            self.women_preferences[agent] = ranks;
+        }
+        Ok(old.changes_to(&self.find_stable_marriage()))
+    }
+
+    /// Adds a man with the given preferences, who gets the index [`num_men`](Self::num_men),
+    /// and solves the problem again. `positions[w]` is the position at which w inserts him in her preference list,
+    /// or None if she does not find him acceptable.
+    /// Returns the couples that changed since the last stable matching.
+    ///
+    /// Returns an error, and changes nothing, if the preferences or the positions are invalid.
+    pub fn add_man(
+        &mut self,
+        preferences: Vec<Woman>,
+        positions: &[Option<usize>],
+    ) -> Result<MatchingChanges, UpdateError> {
+        self.add_agent(Side::Men, preferences, positions)
+    }
+
+    /// Adds a woman with the given preferences, who gets the index [`num_women`](Self::num_women),
+    /// and solves the problem again. `positions[m]` is the position at which m inserts her in his preference list,
+    /// or None if he does not find her acceptable.
+    /// Returns the couples that changed since the last stable matching.
+    ///
+    /// Returns an error, and changes nothing, if the preferences or the positions are invalid.
+    pub fn add_woman(
+        &mut self,
+        preferences: Vec<Man>,
+        positions: &[Option<usize>],
+    ) -> Result<MatchingChanges, UpdateError> {
+        self.add_agent(Side::Women, preferences, positions)
+    }
+
+    /// Withdraws m from the market and solves the problem again.
+    /// He keeps his index, but has no acceptable partner any more, so he stays single.
+    /// Returns the couples that changed since the last stable matching, or an error if m does not exist.
+    pub fn remove_man(&mut self, m: Man) -> Result<MatchingChanges, UpdateError> {
+        self.remove_agent(Side::Men, m)
+    }
+
+    /// Withdraws w from the market and solves the problem again.
+    /// She keeps her index, but has no acceptable partner any more, so she stays single.
+    /// Returns the couples that changed since the last stable matching, or an error if w does not exist.
+    pub fn remove_woman(&mut self, w: Woman) -> Result<MatchingChanges, UpdateError> {
+        self.remove_agent(Side::Women, w)
+    }
+
+    fn add_agent(
+        &mut self,
+        side: Side,
+        preferences: Vec<usize>,
+        positions: &[Option<usize>],
+    ) -> Result<MatchingChanges, UpdateError> {
+        let agent = match side {
+            Side::Men => self.num_men(),
+            Side::Women => self.num_women(),
+        };
+        self.check_new_preferences(side, agent, &preferences)?;
+        // the length of the preference list of each agent of the other side
+        let lengths: Vec<usize> = if side == self.proposing {
+            self.women_preferences
+                .iter()
+                .map(|ranks| ranks.iter().filter(|&&rank| rank != UNACCEPTABLE).count())
+                .collect()
+        } else {
+            self.men_preferences.iter().map(Vec::len).collect()
+        };
+        check_positions(side, positions, &lengths)?;
+        let old = self.current_matching();
+        if side == self.proposing {
+            for (ranks, &position) in self.women_preferences.iter_mut().zip(positions) {
+                insert_rank(ranks, position);
+            }
+            let m = self.men_preferences.len();
+            self.men_preferences.push(preferences);
+            self.next_proposal.push(0);
+            self.free_men.push(m);
+        } else {
+            let w = self.women_engagement.len();
+            // the men that have already proposed after the position of w have to propose to her first
+            self.rewind(
+                positions
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(m, &position)| Some((m, position?))),
+            );
+            for (m, &position) in positions.iter().enumerate() {
+                if let Some(position) = position {
+                    self.men_preferences[m].insert(position, w);
+                }
+            }
+            self.women_engagement.push(None);
+            self.women_preferences.extend(make_partial_rank_matrix(
+                vec![preferences],
+                self.men_preferences.len(),
+            ));
+        }
+        Ok(old.changes_to(&self.find_stable_marriage()))
+    }
+
+    fn remove_agent(&mut self, side: Side, agent: usize) -> Result<MatchingChanges, UpdateError> {
+        self.check_agent(side, agent)?;
+        let old = self.current_matching();
+        if side == self.proposing {
+            self.rewind([(agent, 0)]);
+            self.men_preferences[agent].clear();
+            for ranks in &mut self.women_preferences {
+                remove_rank(ranks, agent);
+            }
+        } else {
+            let positions: Vec<(Man, usize)> = self
+                .men_preferences
+                .iter()
+                .enumerate()
+                .filter_map(|(m, preferences)| {
+                    Some((m, preferences.iter().position(|&w| w == agent)?))
+                })
+                .collect();
+            self.rewind(positions.iter().copied());
+            for &(m, position) in &positions {
+                self.men_preferences[m].remove(position);
+            }
+            self.women_preferences[agent].fill(UNACCEPTABLE);
+        }
+        Ok(old.changes_to(&self.find_stable_marriage()))
+    }
+
+    /// Checks that the agent of the given side exists
+    fn check_agent(&self, side: Side, agent: usize) -> Result<(), UpdateError> {
+        let len = match side {
+            Side::Men => self.num_men(),
+            Side::Women => self.num_women(),
+        };
+        if agent < len {
+            return Ok(());
+        }
+        Err(UpdateError::UnknownAgent { side, agent, len })
+    }
+
+    /// Checks a new preference list for an agent of the given side
+    fn check_new_preferences(
+        &self,
+        side: Side,
+        agent: usize,
+        preferences: &[usize],
+    ) -> Result<(), PreferenceError> {
+        let len = match side {
+            Side::Men => self.num_women(),
+            Side::Women => self.num_men(),
+        };
+        check_preferences(&[preferences.to_vec()], len).map_err(|(_, kind)| PreferenceError {
+            side,
+            row: agent,
+            kind,
+        })
+    }
+
+    /// The men that have proposed to w, with the position of w in their preferences
+    fn proposals_to(&self, w: Woman) -> Vec<(Man, usize)> {
+        (0..self.men_preferences.len())
+            .filter_map(|m| {
+                let proposed = &self.men_preferences[m][..self.next_proposal[m]];
+                Some((m, proposed.iter().position(|&w2| w2 == w)?))
+            })
+            .collect()
+    }
+
+    /// The proposals to w whose outcome may change when her ranks are replaced by `ranks`.
+    /// The man she holds keeps her if he is still acceptable. A man she rejected stays rejected if he is now unacceptable,
+    /// or if she still prefers to him every man that could have caused the rejection under her old ranks.
+    fn outdated_proposals_to(&self, w: Woman, ranks: &[usize]) -> Vec<(Man, usize)> {
+        let old_ranks = &self.women_preferences[w];
+        let holder = self.women_engagement[w];
+        let proposals = self.proposals_to(w);
+        proposals
+            .iter()
+            .copied()
+            .filter(|&(m, _)| {
+                if ranks[m] == UNACCEPTABLE || Some(m) == holder {
+                    return ranks[m] == UNACCEPTABLE && Some(m) == holder;
+                }
+                old_ranks[m] == UNACCEPTABLE
+                    || proposals
+                        .iter()
+                        .any(|&(m2, _)| old_ranks[m2] < old_ranks[m] && ranks[m2] >= ranks[m])
+            })
+            .collect()
+    }
+
+    /// Cancels the proposals that each given man made from the given position in his preferences on,
+    /// and the proposals that were rejected by a woman who had received a cancelled proposal she prefers.
+    /// The proposals that are left are the ones that would have been made if the cancelled ones had never been made,
+    /// so the algorithm still finds the stable matching that is optimal for the men.
+    fn rewind(&mut self, men: impl IntoIterator<Item = (Man, usize)>) {
+        // proposers[w] lists the men that have proposed to w, with the position of w in their preferences
+        let mut proposers = vec![vec![]; self.women_engagement.len()];
+        for (m, preferences) in self.men_preferences.iter().enumerate() {
+            for (i, &w) in preferences[..self.next_proposal[m]].iter().enumerate() {
+                proposers[w].push((m, i));
+            }
+        }
+        let old_next_proposal = self.next_proposal.clone();
+        let mut to_visit: Vec<(Man, usize)> = men.into_iter().collect();
+        // the given men may have new women to propose to, even when none of their proposals is cancelled
+        let mut changed = vec![false; self.men_preferences.len()];
+        for &(m, _) in &to_visit {
+            changed[m] = true;
+        }
+        while let Some((m, position)) = to_visit.pop() {
+            let next = self.next_proposal[m];
+            if position >= next {
+                continue;
+            }
+            self.next_proposal[m] = position;
+            for &w in &self.men_preferences[m][position..next] {
+                for &(m2, i) in &proposers[w] {
+                    if i < self.next_proposal[m2]
+                        && self.woman_accepts(w, m2)
+                        && self.woman_prefers(w, m, m2)
+                    {
+                        to_visit.push((m2, i));
+                    }
+                }
+            }
+        }
+
+        // a man is engaged to the last woman he proposed to, so the rewound men are free again
+        let rewound = |m: Man| self.next_proposal[m] < old_next_proposal[m];
+        for engagement in &mut self.women_engagement {
+            if engagement.is_some_and(rewound) {
+                *engagement = None;
+            }
+        }
+        // the men that are engaged or already waiting to propose must not be added to the free men
+        let mut busy = vec![false; self.men_preferences.len()];
+        for m in self
+            .women_engagement
+            .iter()
+            .flatten()
+            .copied()
+            .chain(self.free_men.iter())
+        {
+            busy[m] = true;
+        }
+        for m in (0..self.men_preferences.len()).filter(|&m| rewound(m) || changed[m]) {
+            if !busy[m] {
+                self.free_men.push(m);
+            }
+        }
+    }
+}
+
+/// Checks that `positions` gives one valid position, or None, for each agent of the other side,
+/// whose preference lists have the given lengths
+fn check_positions(
+    side: Side,
+    positions: &[Option<usize>],
+    lengths: &[usize],
+) -> Result<(), UpdateError> {
+    let side = match side {
+        Side::Men => Side::Women,
+        Side::Women => Side::Men,
+    };
+    if positions.len() < lengths.len() {
+        let agent = positions.len();
+        return Err(UpdateError::MissingPosition { side, agent });
+    }
+    if positions.len() > lengths.len() {
+        let len = lengths.len();
+        return Err(UpdateError::UnknownAgent {
+            side,
+            agent: len,
+            len,
+        });
+    }
+    for (agent, (&position, &len)) in positions.iter().zip(lengths).enumerate() {
+        if let Some(position) = position.filter(|&position| position > len) {
+            return Err(UpdateError::InvalidPosition {
+                side,
+                agent,
+                position,
+                len,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Makes the agent unacceptable, and moves the agents ranked after it one rank up
+fn remove_rank(ranks: &mut [usize], agent: usize) {
+    let removed = std::mem::replace(&mut ranks[agent], UNACCEPTABLE);
+    for rank in ranks
+        .iter_mut()
+        .filter(|rank| **rank != UNACCEPTABLE && **rank > removed)
+    {
+        *rank -= 1;
+    }
+}
+
+/// Gives the rank `position` to a new agent at the end of `ranks`, and moves the agents from that position on one rank down
+fn insert_rank(ranks: &mut Vec<usize>, position: Option<usize>) {
+    if let Some(position) = position {
+        for rank in ranks
+            .iter_mut()
+            .filter(|rank| **rank != UNACCEPTABLE && **rank >= position)
+        {
+            *rank += 1;
+        }
+    }
+    ranks.push(position.unwrap_or(UNACCEPTABLE));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{random_list, random_lists};
+    use crate::*;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_update_preferences() {
+        let men = vec![vec![0, 1, 2], vec![1, 0, 2], vec![0, 1, 2]];
+        let women = vec![vec![1, 0, 2], vec![0, 1, 2], vec![0, 1, 2]];
+        let mut pb = GaleShapley::init(men, women);
+        let matching = pb.find_stable_marriage();
+        assert_eq!(
+            matching.iter().collect::<Vec<_>>(),
+            vec![(0, 0), (1, 1), (2, 2)]
+        );
+        assert_eq!(pb.proposals(), 5);
+        // woman 0 still prefers man 0, who she holds, to man 2, who she rejected, so no proposal is made again
+        let changes = pb
+            .update_preferences(Side::Women, 0, vec![0, 1, 2])
+            .unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(pb.proposals(), 5);
+        let changes = pb
+            .update_preferences(Side::Women, 0, vec![0, 1, 2])
+            .unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(pb.proposals(), 5);
+        let changes = pb.update_preferences(Side::Men, 0, vec![1, 0]).unwrap();
+        assert_eq!(changes.dissolved, vec![(0, 0), (1, 1)]);
+        assert_eq!(changes.formed, vec![(1, 0), (0, 1)]);
+        let proposals = pb.proposals();
+        // woman 1 now prefers man 2, who she rejected, to man 0, who she holds: only man 2 proposes again,
+        // then man 0 goes to woman 0 and man 1 to woman 2. Man 1 stays rejected, he is now unacceptable
+        let changes = pb.update_preferences(Side::Women, 1, vec![2, 0]).unwrap();
+        assert_eq!(changes.dissolved, vec![(1, 0), (0, 1), (2, 2)]);
+        assert_eq!(changes.formed, vec![(0, 0), (2, 1), (1, 2)]);
+        assert_eq!(pb.proposals(), proposals + 3);
+        let error = pb.update_preferences(Side::Men, 1, vec![3]).unwrap_err();
+        assert_eq!(
+            error,
+            UpdateError::InvalidPreferences(PreferenceError {
+                side: Side::Men,
+                row: 1,
+                kind: PreferenceErrorKind::OutOfRange { agent: 3, len: 3 }
+            })
+        );
+        let error = pb.update_preferences(Side::Women, 5, vec![]).unwrap_err();
+        assert_eq!(
+            error,
+            UpdateError::UnknownAgent {
+                side: Side::Women,
+                agent: 5,
+                len: 3
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "woman 5 does not exist, there are only 3"
+        );
+    }
+
+    #[test]
+    fn test_update_preferences_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let (num_men, num_women) = (rng.gen_range(1..6), rng.gen_range(1..6));
+            let mut men = random_lists(&mut rng, num_men, num_women, false);
+            let mut women = random_lists(&mut rng, num_women, num_men, false);
+            let proposing = if rng.gen() { Side::Men } else { Side::Women };
+            let mut pb = GaleShapley::init_with_proposing(men.clone(), women.clone(), proposing);
+            let mut old = pb.find_stable_marriage();
+            for _ in 0..3 {
+                let side = if rng.gen() { Side::Men } else { Side::Women };
+                let (agent, preferences) = match side {
+                    Side::Men => {
+                        let m = rng.gen_range(0..num_men);
+                        men[m] = random_list(&mut rng, num_women);
+                        (m, men[m].clone())
+                    }
+                    Side::Women => {
+                        let w = rng.gen_range(0..num_women);
+                        women[w] = random_list(&mut rng, num_men);
+                        (w, women[w].clone())
+                    }
+                };
+                let changes = pb.update_preferences(side, agent, preferences).unwrap();
+                let mut expected =
+                    GaleShapley::init_with_proposing(men.clone(), women.clone(), proposing);
+                let new = expected.find_stable_marriage();
+                assert_eq!(pb.find_stable_marriage(), new);
+                assert_eq!(changes, old.changes_to(&new));
+                old = new;
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut pb = GaleShapley::init(vec![vec![0, 1], vec![0, 1]], vec![vec![0, 1], vec![0, 1]]);
+        let matching = pb.find_stable_marriage();
+        assert_eq!(matching.iter().collect::<Vec<_>>(), vec![(0, 0), (1, 1)]);
+        // man 2 is the last choice of woman 1, who already has a partner
+        let changes = pb.add_man(vec![1], &[None, Some(2)]).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(pb.num_men(), 3);
+        // woman 2 is the first choice of man 1, and she prefers him to man 2
+        let changes = pb.add_woman(vec![1, 2], &[None, Some(0), Some(1)]).unwrap();
+        assert_eq!(changes.dissolved, vec![(1, 1)]);
+        assert_eq!(changes.formed, vec![(2, 1), (1, 2)]);
+        let changes = pb.remove_man(0).unwrap();
+        assert_eq!(changes.dissolved, vec![(0, 0)]);
+        assert!(changes.formed.is_empty());
+        let changes = pb.remove_woman(2).unwrap();
+        assert_eq!(changes.dissolved, vec![(1, 2)]);
+        assert_eq!(changes.formed, vec![(1, 0)]);
+        assert!(pb.add_man(vec![3], &[None; 3]).is_err());
+        let error = pb.add_man(vec![0], &[None, Some(3), None]).unwrap_err();
+        assert_eq!(
+            error,
+            UpdateError::InvalidPosition {
+                side: Side::Women,
+                agent: 1,
+                position: 3,
+                len: 2
+            }
+        );
+        let error = pb.add_woman(vec![], &[None]).unwrap_err();
+        assert_eq!(
+            error,
+            UpdateError::MissingPosition {
+                side: Side::Men,
+                agent: 1
+            }
+        );
+        let error = pb.remove_man(3).unwrap_err();
+        assert_eq!(
+            error,
+            UpdateError::UnknownAgent {
+                side: Side::Men,
+                agent: 3,
+                len: 3
+            }
+        );
+        assert_eq!((pb.num_men(), pb.num_women()), (3, 3));
+    }
+
+    /// Adds an agent with random preferences to `side`, whose lists are `lists`,
+    /// and inserts them at random positions in some of the `others` lists
+    fn add_random(
+        rng: &mut impl Rng,
+        pb: &mut GaleShapley,
+        side: Side,
+        lists: &mut Vec<Vec<usize>>,
+        others: &mut [Vec<usize>],
+    ) -> MatchingChanges {
+        let preferences = random_list(rng, others.len());
+        let positions: Vec<Option<usize>> = (others.iter())
+            .map(|list| rng.gen::<bool>().then(|| rng.gen_range(0..=list.len())))
+            .collect();
+        for (list, position) in others.iter_mut().zip(&positions) {
+            if let Some(position) = *position {
+                list.insert(position, lists.len());
+            }
+        }
+        lists.push(preferences.clone());
+        match side {
+            Side::Men => pb.add_man(preferences, &positions).unwrap(),
+            Side::Women => pb.add_woman(preferences, &positions).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            let (num_men, num_women) = (rng.gen_range(1..5), rng.gen_range(1..5));
+            let mut men = random_lists(&mut rng, num_men, num_women, false);
+            let mut women = random_lists(&mut rng, num_women, num_men, false);
+            let proposing = if rng.gen() { Side::Men } else { Side::Women };
+            let mut pb = GaleShapley::init_with_proposing(men.clone(), women.clone(), proposing);
+            let mut old = pb.find_stable_marriage();
+            for _ in 0..4 {
+                let changes = match rng.gen_range(0..4) {
+                    0 => add_random(&mut rng, &mut pb, Side::Men, &mut men, &mut women),
+                    1 => add_random(&mut rng, &mut pb, Side::Women, &mut women, &mut men),
+                    2 => {
+                        let m = rng.gen_range(0..men.len());
+                        men[m].clear();
+                        women.iter_mut().for_each(|list| list.retain(|&m2| m2 != m));
+                        pb.remove_man(m).unwrap()
+                    }
+                    _ => {
+                        let w = rng.gen_range(0..women.len());
+                        women[w].clear();
+                        men.iter_mut().for_each(|list| list.retain(|&w2| w2 != w));
+                        pb.remove_woman(w).unwrap()
+                    }
+                };
+                let new = GaleShapley::init_with_proposing(men.clone(), women.clone(), proposing)
+                    .find_stable_marriage();
+                assert_eq!(pb.find_stable_marriage(), new);
+                assert_eq!(changes, old.changes_to(&new));
+                old = new;
+            }
+        }
+    }
+}
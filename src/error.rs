@@ -0,0 +1,221 @@
+//! Errors in the preferences given to the algorithms, and in the updates of an instance.
+
+use crate::{Man, Side, Woman};
+use std::fmt;
+
+/// A preference list that is not valid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PreferenceError {
+    /// Whose preferences are invalid
+    pub side: Side,
+    /// The index of the invalid list
+    pub row: usize,
+    /// What is wrong with it
+    pub kind: PreferenceErrorKind,
+}
+
+/// What is wrong with a preference list
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreferenceErrorKind {
+    /// The list contains an agent that does not exist on the other side, which has `len` agents
+    OutOfRange { agent: usize, len: usize },
+    /// The list contains the same agent twice
+    Duplicate(usize),
+    /// The list has `found` entries, but one entry per agent of the other side is expected
+    WrongLength { expected: usize, found: usize },
+}
+
+impl fmt::Display for PreferenceErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreferenceErrorKind::OutOfRange { agent, len } => {
+                write!(f, "{agent} is out of range, there are only {len} agents")
+            }
+            PreferenceErrorKind::Duplicate(agent) => write!(f, "{agent} appears twice"),
+            PreferenceErrorKind::WrongLength { expected, found } => {
+                write!(f, "expected {expected} entries, found {found}")
+            }
+        }
+    }
+}
+
+/// How an agent of the given side is called in the messages
+fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Men => "man",
+        Side::Women => "woman",
+    }
+}
+
+impl fmt::Display for PreferenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid preferences for {} {}: {}",
+            side_name(self.side),
+            self.row,
+            self.kind
+        )
+    }
+}
+
+impl std::error::Error for PreferenceError {}
+
+/// An update of an instance that cannot be applied, given to
+/// [`update_preferences`](crate::GaleShapley::update_preferences), [`add_man`](crate::GaleShapley::add_man),
+/// [`add_woman`](crate::GaleShapley::add_woman), [`remove_man`](crate::GaleShapley::remove_man)
+/// or [`remove_woman`](crate::GaleShapley::remove_woman)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpdateError {
+    /// The agent does not exist, on a side that has `len` agents
+    UnknownAgent {
+        side: Side,
+        agent: usize,
+        len: usize,
+    },
+    /// No position is given for the agent to insert the new agent in their list
+    MissingPosition { side: Side, agent: usize },
+    /// The agent cannot insert the new agent at `position`, their list only has `len` agents
+    InvalidPosition {
+        side: Side,
+        agent: usize,
+        position: usize,
+        len: usize,
+    },
+    /// The new preference list is invalid
+    InvalidPreferences(PreferenceError),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            UpdateError::UnknownAgent { side, agent, len } => {
+                let side = side_name(side);
+                write!(f, "{side} {agent} does not exist, there are only {len}")
+            }
+            UpdateError::MissingPosition { side, agent } => {
+                let side = side_name(side);
+                write!(
+                    f,
+                    "no position is given for {side} {agent} to insert the new agent"
+                )
+            }
+            UpdateError::InvalidPosition {
+                side,
+                agent,
+                position,
+                len,
+            } => {
+                let side = side_name(side);
+                write!(
+                    f,
+                    "{side} {agent} cannot insert the new agent at position {position}, their list only has {len} agents"
+                )
+            }
+            UpdateError::InvalidPreferences(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UpdateError::InvalidPreferences(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<PreferenceError> for UpdateError {
+    fn from(error: PreferenceError) -> Self {
+        UpdateError::InvalidPreferences(error)
+    }
+}
+
+/// Checks that each line only contains distinct agents in `0..len`.
+/// Returns the index of the first invalid line with the problem found in it.
+pub(crate) fn check_preferences(
+    p: &[Vec<usize>],
+    len: usize,
+) -> Result<(), (usize, PreferenceErrorKind)> {
+    for (row, line) in p.iter().enumerate() {
+        let mut seen = vec![false; len];
+        for &agent in line {
+            let kind = if agent >= len {
+                PreferenceErrorKind::OutOfRange { agent, len }
+            } else if seen[agent] {
+                PreferenceErrorKind::Duplicate(agent)
+            } else {
+                seen[agent] = true;
+                continue;
+            };
+            return Err((row, kind));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that each line has one entry per agent in `0..len`.
+/// Returns the index of the first invalid line with the problem found in it.
+pub(crate) fn check_lengths(
+    p: &[Vec<usize>],
+    len: usize,
+) -> Result<(), (usize, PreferenceErrorKind)> {
+    match p.iter().position(|line| line.len() != len) {
+        Some(row) => Err((
+            row,
+            PreferenceErrorKind::WrongLength {
+                expected: len,
+                found: p[row].len(),
+            },
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks the rank matrices of both sides, as given to [`GaleShapley::init_with_ties`](crate::GaleShapley::init_with_ties)
+pub(crate) fn check_tied_instance(
+    men_ranks: &[Vec<usize>],
+    women_ranks: &[Vec<usize>],
+) -> Result<(), PreferenceError> {
+    let error = |side| move |(row, kind)| PreferenceError { side, row, kind };
+    check_lengths(men_ranks, women_ranks.len()).map_err(error(Side::Men))?;
+    check_lengths(women_ranks, men_ranks.len()).map_err(error(Side::Women))
+}
+
+/// Checks the preferences of both sides, as given to [`GaleShapley::init`](crate::GaleShapley::init)
+pub(crate) fn check_instance(
+    men_preferences: &[Vec<Woman>],
+    women_preferences: &[Vec<Man>],
+) -> Result<(), PreferenceError> {
+    let error = |side| move |(row, kind)| PreferenceError { side, row, kind };
+    check_preferences(men_preferences, women_preferences.len()).map_err(error(Side::Men))?;
+    check_preferences(women_preferences, men_preferences.len()).map_err(error(Side::Women))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_instance() {
+        assert_eq!(check_instance(&[vec![1, 0]], &[vec![0], vec![]]), Ok(()));
+        assert_eq!(
+            check_instance(&[vec![0, 2]], &[vec![0], vec![0]]),
+            Err(PreferenceError {
+                side: Side::Men,
+                row: 0,
+                kind: PreferenceErrorKind::OutOfRange { agent: 2, len: 2 }
+            })
+        );
+        let error = check_instance(&[vec![0], vec![0]], &[vec![0], vec![1, 0, 1]]).unwrap_err();
+        assert_eq!(error.side, Side::Women);
+        assert_eq!(error.row, 1);
+        assert_eq!(error.kind, PreferenceErrorKind::Duplicate(1));
+        assert_eq!(
+            error.to_string(),
+            "invalid preferences for woman 1: 1 appears twice"
+        );
+    }
+}
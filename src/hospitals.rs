@@ -0,0 +1,342 @@
+//! Many-to-one variant of the stable marriage problem, where each hospital can take several residents.
+
+use crate::{
+    check_instance, make_men_preferences, make_partial_rank_matrix, PreferenceError, UNACCEPTABLE,
+};
+
+pub type Resident = usize;
+pub type Hospital = usize;
+
+/// Which side makes the proposals. The resulting matching is optimal for that side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proposing {
+    Residents,
+    Hospitals,
+}
+
+/// An instance of the hospitals/residents problem, solved by a many-to-one version of the Gale-Shapley algorithm.
+///
+/// Each resident is assigned to at most one hospital, and each hospital takes at most its capacity of residents.
+pub struct HospitalsResidents {
+    proposing: Proposing,
+
+    /// residents or hospitals that can still propose, depending on who proposes
+    free: Vec<usize>,
+
+    /// residents_preferences[r][N-i] is the ith prefered hospital of r
+    residents_preferences: Vec<Vec<Hospital>>,
+
+    /// hospitals_preferences[h][N-i] is the ith prefered resident of h
+    hospitals_preferences: Vec<Vec<Resident>>,
+
+    /// residents_ranks[r][h] is the rank of h in r's preferences, or UNACCEPTABLE
+    residents_ranks: Vec<Vec<usize>>,
+
+    /// hospitals_ranks[h][r] is the rank of r in h's preferences, or UNACCEPTABLE
+    hospitals_ranks: Vec<Vec<usize>>,
+
+    /// capacities[h] is the maximum number of residents h can take
+    capacities: Vec<usize>,
+
+    /// assignees[h] are the residents currently assigned to h
+    assignees: Vec<Vec<Resident>>,
+
+    /// residents_assignment[r] is the hospital r is currently assigned to
+    residents_assignment: Vec<Option<Hospital>>,
+}
+
+impl HospitalsResidents {
+    /// Creates an instance where each agent lists the partners they find acceptable, most preferred first,
+    /// and hospital h can take at most `capacities[h]` residents.
+    pub fn init(
+        residents_preferences: Vec<Vec<Hospital>>,
+        hospitals_preferences: Vec<Vec<Resident>>,
+        capacities: Vec<usize>,
+        proposing: Proposing,
+    ) -> HospitalsResidents {
+        HospitalsResidents::try_init(
+            residents_preferences,
+            hospitals_preferences,
+            capacities,
+            proposing,
+        )
+        .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`init`](Self::init), but returns an error describing the first invalid preference list
+    /// instead of panicking. The residents are reported as [`Side::Men`](crate::Side::Men)
+    /// and the hospitals as [`Side::Women`](crate::Side::Women).
+    ///
+    /// Still panics if there is not exactly one capacity per hospital.
+    pub fn try_init(
+        residents_preferences: Vec<Vec<Hospital>>,
+        hospitals_preferences: Vec<Vec<Resident>>,
+        capacities: Vec<usize>,
+        proposing: Proposing,
+    ) -> Result<HospitalsResidents, PreferenceError> {
+        check_instance(&residents_preferences, &hospitals_preferences)?;
+        let num_residents = residents_preferences.len();
+        let num_hospitals = hospitals_preferences.len();
+        assert_eq!(
+            capacities.len(),
+            num_hospitals,
+            "expected one capacity per hospital"
+        );
+        let free = match proposing {
+            Proposing::Residents => (0..num_residents).rev().collect(),
+            Proposing::Hospitals => (0..num_hospitals).rev().collect(),
+        };
+        Ok(HospitalsResidents {
+            proposing,
+            free,
+            residents_ranks: make_partial_rank_matrix(residents_preferences.clone(), num_hospitals),
+            hospitals_ranks: make_partial_rank_matrix(hospitals_preferences.clone(), num_residents),
+            residents_preferences: make_men_preferences(residents_preferences),
+            hospitals_preferences: make_men_preferences(hospitals_preferences),
+            capacities,
+            assignees: vec![vec![]; num_hospitals],
+            residents_assignment: vec![None; num_residents],
+        })
+    }
+
+    /// Returns the residents currently assigned to h
+    pub fn assignees(&self, h: Hospital) -> &[Resident] {
+        &self.assignees[h]
+    }
+
+    /// Returns the hospital r is currently assigned to
+    pub fn current_resident_assignment(&self, r: Resident) -> Option<Hospital> {
+        self.residents_assignment[r]
+    }
+
+    /// Whether h has no free position left
+    fn is_full(&self, h: Hospital) -> bool {
+        self.assignees[h].len() >= self.capacities[h]
+    }
+
+    /// The resident h likes least among its assignees
+    fn worst_assignee(&self, h: Hospital) -> Option<Resident> {
+        let ranks = &self.hospitals_ranks[h];
+        self.assignees[h].iter().copied().max_by_key(|&r| ranks[r])
+    }
+
+    /// Whether r is in h's preferences and h prefers r over r2
+    fn hospital_prefers(&self, h: Hospital, r: Resident, r2: Resident) -> bool {
+        self.hospitals_ranks[h][r] < self.hospitals_ranks[h][r2]
+    }
+
+    /// Whether h is in r's preferences and r prefers h over its current assignment
+    fn resident_accepts(&self, r: Resident, h: Hospital) -> bool {
+        let ranks = &self.residents_ranks[r];
+        ranks[h] != UNACCEPTABLE
+            && self.residents_assignment[r].is_none_or(|h2| ranks[h] < ranks[h2])
+    }
+
+    /// marks r as assigned to h
+    fn assign(&mut self, r: Resident, h: Hospital) {
+        self.assignees[h].push(r);
+        self.residents_assignment[r] = Some(h);
+    }
+
+    /// removes r from the assignees of h
+    fn unassign(&mut self, r: Resident, h: Hospital) {
+        self.assignees[h].retain(|&r2| r2 != r);
+        self.residents_assignment[r] = None;
+    }
+
+    /// Makes the next proposal. If we have reached a stable state,
+    /// returns None, otherwise return the (resident, hospital) couple concerned by the proposal
+    pub fn next_engagement_round(&mut self) -> Option<(Resident, Hospital)> {
+        match self.proposing {
+            Proposing::Residents => self.next_resident_proposal(),
+            Proposing::Hospitals => self.next_hospital_proposal(),
+        }
+    }
+
+    fn next_resident_proposal(&mut self) -> Option<(Resident, Hospital)> {
+        let (r, h) = loop {
+            let r = *self.free.last()?;
+            if let Some(h) = self.residents_preferences[r].pop() {
+                break (r, h);
+            }
+            // r has been rejected by every hospital, it stays unassigned
+            self.free.pop();
+        };
+        if self.hospitals_ranks[h][r] == UNACCEPTABLE {
+            return Some((r, h));
+        }
+        if !self.is_full(h) {
+            self.free.pop();
+            self.assign(r, h);
+        } else if let Some(r2) = self.worst_assignee(h) {
+            if self.hospital_prefers(h, r, r2) {
+                // h prefers r over its worst assignee r2
+                self.unassign(r2, h);
+                self.free.pop();
+                self.free.push(r2);
+                self.assign(r, h);
+            }
+        }
+        Some((r, h))
+    }
+
+    fn next_hospital_proposal(&mut self) -> Option<(Resident, Hospital)> {
+        let (r, h) = loop {
+            let h = *self.free.last()?;
+            if !self.is_full(h) {
+                if let Some(r) = self.hospitals_preferences[h].pop() {
+                    break (r, h);
+                }
+            }
+            // h is full or has proposed to every resident
+            self.free.pop();
+        };
+        if self.resident_accepts(r, h) {
+            if let Some(h2) = self.residents_assignment[r] {
+                if self.is_full(h2) {
+                    // h2 gets a free position back
+                    self.free.push(h2);
+                }
+                self.unassign(r, h2);
+            }
+            self.assign(r, h);
+        }
+        Some((r, h))
+    }
+
+    /// Returns the final stable assignment, as (resident, hospital) couples in the order of the residents
+    pub fn find_stable_assignment(&mut self) -> impl Iterator<Item = (Resident, Hospital)> + '_ {
+        while self.next_engagement_round().is_some() {}
+        self.residents_assignment
+            .iter()
+            .enumerate()
+            .filter_map(|(r, h)| Some((r, (*h)?)))
+    }
+
+    /// Number of residents
+    pub fn num_residents(&self) -> usize {
+        self.residents_assignment.len()
+    }
+
+    /// Number of hospitals
+    pub fn num_hospitals(&self) -> usize {
+        self.assignees.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_lists;
+    use crate::{make_partial_rank_matrix, PreferenceErrorKind, Side};
+    use rand::{Rng, SeedableRng};
+
+    fn instance(proposing: Proposing) -> HospitalsResidents {
+        let residents_preferences = vec![vec![0, 1], vec![1, 0], vec![0, 1], vec![0, 1]];
+        let hospitals_preferences = vec![vec![1, 2, 0, 3], vec![0, 2, 1, 3]];
+        HospitalsResidents::init(
+            residents_preferences,
+            hospitals_preferences,
+            vec![2, 1],
+            proposing,
+        )
+    }
+
+    #[test]
+    fn test_resident_proposing() {
+        let mut pb = instance(Proposing::Residents);
+        let actual: Vec<(Resident, Hospital)> = pb.find_stable_assignment().collect();
+        assert_eq!(actual, vec![(0, 0), (1, 1), (2, 0)]);
+        assert_eq!(pb.assignees(0), [0, 2]);
+        assert_eq!(pb.current_resident_assignment(3), None);
+    }
+
+    #[test]
+    fn test_hospital_proposing() {
+        let mut pb = instance(Proposing::Hospitals);
+        let actual: Vec<(Resident, Hospital)> = pb.find_stable_assignment().collect();
+        assert_eq!(actual, vec![(0, 1), (1, 0), (2, 0)]);
+        assert_eq!(pb.assignees(0), [1, 2]);
+        assert_eq!(pb.assignees(1), [0]);
+    }
+
+    #[test]
+    fn test_hospital_proposing_displaced_resident() {
+        // resident 0 leaves hospital 0 for hospital 1, and hospital 0 proposes again
+        let residents_preferences = vec![vec![1, 0], vec![0]];
+        let hospitals_preferences = vec![vec![0, 1], vec![0]];
+        let mut pb = HospitalsResidents::init(
+            residents_preferences,
+            hospitals_preferences,
+            vec![1, 1],
+            Proposing::Hospitals,
+        );
+        assert_eq!(pb.next_engagement_round(), Some((0, 0)));
+        assert_eq!(pb.next_engagement_round(), Some((0, 1)));
+        assert_eq!(pb.next_engagement_round(), Some((1, 0)));
+        assert_eq!(pb.next_engagement_round(), None);
+        assert_eq!(pb.assignees(0), [1]);
+        assert_eq!(pb.assignees(1), [0]);
+    }
+
+    #[test]
+    fn test_invalid_preferences() {
+        let result = HospitalsResidents::try_init(
+            vec![vec![0], vec![0, 2]],
+            vec![vec![1, 0]],
+            vec![1],
+            Proposing::Residents,
+        );
+        let error = result.err().unwrap();
+        assert_eq!((error.side, error.row), (Side::Men, 1));
+        assert_eq!(
+            error.kind,
+            PreferenceErrorKind::OutOfRange { agent: 2, len: 1 }
+        );
+    }
+
+    #[test]
+    fn test_stable_assignment_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            let (num_residents, num_hospitals) = (rng.gen_range(1..8), rng.gen_range(1..4));
+            let residents = random_lists(&mut rng, num_residents, num_hospitals, false);
+            let hospitals = random_lists(&mut rng, num_hospitals, num_residents, false);
+            let capacities: Vec<usize> = (0..num_hospitals).map(|_| rng.gen_range(0..4)).collect();
+            let residents_ranks = make_partial_rank_matrix(residents.clone(), num_hospitals);
+            let hospitals_ranks = make_partial_rank_matrix(hospitals.clone(), num_residents);
+            for proposing in [Proposing::Residents, Proposing::Hospitals] {
+                let mut pb = HospitalsResidents::init(
+                    residents.clone(),
+                    hospitals.clone(),
+                    capacities.clone(),
+                    proposing,
+                );
+                let mut assignment = vec![None; num_residents];
+                for (r, h) in pb.find_stable_assignment() {
+                    assert_ne!(residents_ranks[r][h], UNACCEPTABLE);
+                    assert_ne!(hospitals_ranks[h][r], UNACCEPTABLE);
+                    assignment[r] = Some(h);
+                }
+                for h in 0..num_hospitals {
+                    let assignees = pb.assignees(h);
+                    assert!(assignees.len() <= capacities[h]);
+                    assert!(assignees.iter().all(|&r| assignment[r] == Some(h)));
+                    let worst = assignees.iter().map(|&r| hospitals_ranks[h][r]).max();
+                    for r in 0..num_residents {
+                        let acceptable = residents_ranks[r][h] != UNACCEPTABLE
+                            && hospitals_ranks[h][r] != UNACCEPTABLE;
+                        let resident_wants = assignment[r]
+                            .is_none_or(|h2| residents_ranks[r][h] < residents_ranks[r][h2]);
+                        let hospital_wants = assignees.len() < capacities[h]
+                            || worst.is_some_and(|worst| hospitals_ranks[h][r] < worst);
+                        assert!(
+                            !(acceptable && resident_wants && hospital_wants),
+                            "({r}, {h}) is a blocking pair"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,62 +1,259 @@
-use std::sync::atomic::AtomicUsize;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod dynamic;
+mod error;
+pub mod hospitals;
+mod matching;
+mod observer;
+mod optimal;
+mod order;
+pub mod roommates;
+mod rotations;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(test)]
+mod test_utils;
+mod ties;
+mod verify;
+
+use error::{check_instance, check_lengths, check_preferences, check_tied_instance};
+pub use error::{PreferenceError, PreferenceErrorKind, UpdateError};
+pub use matching::{Matching, MatchingChanges, Pairs};
+pub use observer::EngagementObserver;
+pub use optimal::Fairness;
+use order::FreeMen;
+pub use order::ProposalOrder;
+pub use rotations::{RotationPoset, StableMarriages};
+pub use ties::{strongly_stable_marriage, super_stable_marriage};
+pub use verify::{verify_matching, MatchingIssue, MatchingReport};
 
 pub type Man = usize;
 pub type Woman = usize;
 
+/// Rank given to the partners that are missing from a preference list
+pub const UNACCEPTABLE: usize = usize::MAX;
+
+/// How ties between equally ranked partners are broken by [`GaleShapley::init_with_ties`].
+/// Whatever the rule, the resulting matching is weakly stable:
+/// no man and woman both strictly prefer each other to their partners.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TieBreak {
+    /// Among equally ranked partners, the one with the lowest index is preferred
+    LowestIndex,
+    /// Among equally ranked partners, the one with the highest index is preferred
+    HighestIndex,
+    /// Ties are broken uniformly at random, deterministically from the given seed
+    Random(u64),
+}
+
+/// One side of the market
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Side {
+    Men,
+    Women,
+}
+
+/// The Gale-Shapley algorithm, with men proposing to women.
+///
+/// When the instance is created with [`init_with_proposing`](GaleShapley::init_with_proposing)
+/// and women propose, the roles are swapped internally: the methods that drive and inspect the algorithm step by step
+/// ([`next_engagement_round`](GaleShapley::next_engagement_round), [`best_woman_for`](GaleShapley::best_woman_for),
+/// [`current_woman_engagement`](GaleShapley::current_woman_engagement), [`man_preferences`](GaleShapley::man_preferences),
+/// [`men_rank_matrix`](GaleShapley::men_rank_matrix) and [`women_preferences`](GaleShapley::women_preferences)) call the proposers men and the receivers women.
+/// The results ([`find_stable_marriage`](GaleShapley::find_stable_marriage), [`has_stable_mariage_with`](GaleShapley::has_stable_mariage_with),
+/// [`unmatched_men`](GaleShapley::unmatched_men), [`unmatched_women`](GaleShapley::unmatched_women))
+/// and the sizes always refer to the real men and women.
+///
+/// Cloning an instance copies its preferences and its progress; see [`checkpoint`](GaleShapley::checkpoint)
+/// to only save the progress. With the `serde` feature, instances can also be serialized at any point,
+/// and deserialized to resume solving where it stopped.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "serialization::RawGaleShapley")
+)]
 pub struct GaleShapley {
-    free_men: Vec<Man>,
+    /// The side that makes the proposals, called men in the fields below
+    proposing: Side,
 
-    /// men_preferences[m][N-i] is the ith prefered woman of m
+    free_men: FreeMen,
+
+    /// men_preferences[m][i] is the ith prefered woman of m
     men_preferences: Vec<Vec<Woman>>,
 
-    /// women_preferences[w][m] is the rank of m in w's preferences
+    /// next_proposal[m] is the position in m's preferences of the next woman he will propose to
+    next_proposal: Vec<usize>,
+
+    /// women_preferences[w][m] is the rank of m in w's preferences, or UNACCEPTABLE
     women_preferences: Vec<Vec<usize>>,
 
     /// women_engagement[w] is the man w is currently engaged to
     women_engagement: Vec<Option<Man>>,
+
+    /// Number of proposals made so far
+    proposals: usize,
 }
 
 impl GaleShapley {
+    /// Creates an instance with `men_preferences.len()` men and `women_preferences.len()` women.
+    /// The two sides do not need to have the same size: the agents on the larger side
+    /// that cannot be matched stay single.
+    ///
+    /// Preference lists can be incomplete: each agent only lists the partners they find acceptable,
+    /// and a man and a woman are never matched unless they both list each other.
+    ///
+    /// Panics if a list contains an agent that does not exist, or the same agent twice.
+    /// See [`try_init`](Self::try_init) to get an error instead.
     pub fn init(men_preferences: Vec<Vec<Woman>>, women_preferences: Vec<Vec<Man>>) -> GaleShapley {
+        GaleShapley::try_init(men_preferences, women_preferences).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`init`](Self::init), but returns an error describing the first invalid preference list
+    /// instead of panicking.
+    pub fn try_init(
+        men_preferences: Vec<Vec<Woman>>,
+        women_preferences: Vec<Vec<Man>>,
+    ) -> Result<GaleShapley, PreferenceError> {
+        check_instance(&men_preferences, &women_preferences)?;
+        Ok(GaleShapley::init_unchecked(
+            men_preferences,
+            women_preferences,
+        ))
+    }
+
+    /// Creates an instance from valid preferences, where the men propose
+    fn init_unchecked(
+        men_preferences: Vec<Vec<Woman>>,
+        women_preferences: Vec<Vec<Man>>,
+    ) -> GaleShapley {
         let num_men = men_preferences.len();
         let num_women = women_preferences.len();
-        assert_eq!(num_men, num_women);
 
         GaleShapley {
-            free_men: (0..num_men).rev().collect(),
-            men_preferences: make_men_preferences(men_preferences),
-            women_preferences: make_rank_matrix(women_preferences),
+            proposing: Side::Men,
+            free_men: FreeMen::new(ProposalOrder::Stack, num_men),
+            men_preferences,
+            next_proposal: vec![0; num_men],
+            women_preferences: make_partial_rank_matrix(women_preferences, num_men),
             women_engagement: vec![None; num_women],
+            proposals: 0,
         }
     }
 
+    /// Creates an instance where the `proposing` side makes the proposals.
+    /// The result is the stable matching that is optimal for that side, reported as `(Man, Woman)` pairs either way.
+    ///
+    /// Panics on invalid preferences, like [`init`](Self::init).
+    pub fn init_with_proposing(
+        men_preferences: Vec<Vec<Woman>>,
+        women_preferences: Vec<Vec<Man>>,
+        proposing: Side,
+    ) -> GaleShapley {
+        GaleShapley::try_init_with_proposing(men_preferences, women_preferences, proposing)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`init_with_proposing`](Self::init_with_proposing), but returns an error describing
+    /// the first invalid preference list instead of panicking.
+    pub fn try_init_with_proposing(
+        men_preferences: Vec<Vec<Woman>>,
+        women_preferences: Vec<Vec<Man>>,
+        proposing: Side,
+    ) -> Result<GaleShapley, PreferenceError> {
+        check_instance(&men_preferences, &women_preferences)?;
+        let mut pb = match proposing {
+            Side::Men => GaleShapley::init_unchecked(men_preferences, women_preferences),
+            Side::Women => GaleShapley::init_unchecked(women_preferences, men_preferences),
+        };
+        pb.proposing = proposing;
+        Ok(pb)
+    }
+
+    /// Creates an instance from preferences with ties.
+    /// `men_ranks[m][w]` is the rank of w in m's preferences and `women_ranks[w][m]` the rank of m in w's preferences.
+    /// Equal ranks mean indifference, and [`UNACCEPTABLE`] marks a partner that is not acceptable.
+    ///
+    /// Ties are broken once and for all according to `tie_break`, so that the algorithm runs on strict preferences,
+    /// and [`men_rank_matrix`](Self::men_rank_matrix) and [`women_preferences`](Self::women_preferences)
+    /// return the strict ranks after tie breaking.
+    ///
+    /// Panics if a row does not rank every agent of the other side.
+    /// See [`try_init_with_ties`](Self::try_init_with_ties) to get an error instead.
+    pub fn init_with_ties(
+        men_ranks: Vec<Vec<usize>>,
+        women_ranks: Vec<Vec<usize>>,
+        tie_break: TieBreak,
+    ) -> GaleShapley {
+        GaleShapley::try_init_with_ties(men_ranks, women_ranks, tie_break)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as [`init_with_ties`](Self::init_with_ties), but returns an error describing the first invalid row
+    /// instead of panicking.
+    pub fn try_init_with_ties(
+        men_ranks: Vec<Vec<usize>>,
+        women_ranks: Vec<Vec<usize>>,
+        tie_break: TieBreak,
+    ) -> Result<GaleShapley, PreferenceError> {
+        check_tied_instance(&men_ranks, &women_ranks)?;
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(match tie_break {
+            TieBreak::Random(seed) => seed,
+            _ => 0,
+        });
+        let men_preferences = break_ties(men_ranks, tie_break, &mut rng);
+        let women_preferences = break_ties(women_ranks, tie_break, &mut rng);
+        Ok(GaleShapley::init_unchecked(
+            men_preferences,
+            women_preferences,
+        ))
+    }
+
     ///Creates a random Gale Shapley instance with n men and women
     pub fn init_random(n: usize) -> GaleShapley {
         GaleShapley {
-            free_men: (0..n).rev().collect(),
+            proposing: Side::Men,
+            free_men: FreeMen::new(ProposalOrder::Stack, n),
             men_preferences: rand_pref_matrix(n),
+            next_proposal: vec![0; n],
             women_preferences: rand_pref_matrix(n),
             women_engagement: vec![None; n],
+            proposals: 0,
         }
     }
 
     fn next_free_man(&self) -> Option<Man> {
-        self.free_men.last().copied()
+        self.free_men.next()
+    }
+
+    /// Changes the order in which the free men propose, from now on.
+    /// The final matching does not depend on it, but the number of proposals does.
+    /// The men propose in the [`Stack`](ProposalOrder::Stack) order by default.
+    pub fn set_proposal_order(&mut self, order: ProposalOrder) {
+        if let ProposalOrder::Priority(priorities) = &order {
+            assert_eq!(
+                priorities.len(),
+                self.men_preferences.len(),
+                "expected one priority per proposer"
+            );
+        }
+        self.free_men.set_order(order);
     }
 
-    /// Returns the woman that m wants currently wants the most
-    pub fn best_woman_for(&self, m: Man) -> Woman {
-        self.men_preferences[m]
-            .last()
-            .copied()
-            .expect("internal error: man has no more preferences")
+    /// Returns the woman that m wants currently wants the most,
+    /// or None if he has already proposed to every woman he finds acceptable
+    pub fn best_woman_for(&self, m: Man) -> Option<Woman> {
+        self.men_preferences[m].get(self.next_proposal[m]).copied()
     }
 
-    /// Returns the woman that m wants currently wants the most
-    fn take_best_woman_for(&mut self, m: Man) -> Woman {
-        self.men_preferences[m]
-            .pop()
-            .expect("internal error: man has no more preferences")
+    /// Returns the woman that m wants currently wants the most, and moves on to the next one
+    fn take_best_woman_for(&mut self, m: Man) -> Option<Woman> {
+        let w = self.best_woman_for(m)?;
+        self.next_proposal[m] += 1;
+        self.proposals += 1;
+        Some(w)
     }
 
     /// Returns the man that w is engaged to
@@ -64,6 +261,11 @@ impl GaleShapley {
         self.women_engagement[w]
     }
 
+    /// Whether m is in w's preference list
+    fn woman_accepts(&self, w: Woman, m: Man) -> bool {
+        self.women_preferences[w][m] != UNACCEPTABLE
+    }
+
     /// Whether w prefers m1 over m2
     fn woman_prefers(&self, w: Woman, m1: Man, m2: Man) -> bool {
         let prefs = &self.women_preferences[w];
@@ -85,34 +287,232 @@ impl GaleShapley {
     /// Tries to engage the next free man. If we have reached a stable state,
     /// returns None, otherwise return the (man, woman) couple that proposed
     pub fn next_engagement_round(&mut self) -> Option<(Man, Woman)> {
-        let m = self.next_free_man()?;
-        let w = self.take_best_woman_for(m);
+        self.next_engagement_round_observed(&mut ())
+    }
+
+    /// Same as [`next_engagement_round`](Self::next_engagement_round), and tells the observer what happens
+    pub fn next_engagement_round_observed(
+        &mut self,
+        observer: &mut impl EngagementObserver,
+    ) -> Option<(Man, Woman)> {
+        let (m, w) = loop {
+            let m = self.next_free_man()?;
+            if let Some(w) = self.take_best_woman_for(m) {
+                break (m, w);
+            }
+            // m has been rejected by every woman, he stays single
+            self.free_men.pop();
+        };
+        observer.proposal(m, w);
         if let Some(m2) = self.current_woman_engagement(w) {
             if self.woman_prefers(w, m, m2) {
                 // w prefers m over her current partner m2
                 self.engage(m, w);
                 self.free_from_engagement(m2);
+                observer.divorce(m2, w);
+                observer.acceptance(m, w);
+            } else {
+                observer.rejection(m, w);
             }
-        } else {
+        } else if self.woman_accepts(w, m) {
             self.engage(m, w);
+            observer.acceptance(m, w);
+        } else {
+            observer.rejection(m, w);
         }
         Some((m, w))
     }
 
-    /// Returns the final stable marriage
-    pub fn find_stable_marriage(&mut self) -> impl Iterator<Item = (Man, Woman)> + '_ {
-        while let Some((_m, _w)) = self.next_engagement_round() {
-            // println!("{_m} proposes to {_w}. Engagements: {:?}. Free men: {:?}", self.women_engagement, self.free_men)
+    /// Returns the final stable marriage.
+    /// Agents that stay single are left out of its pairs, see [`Matching::unmatched_men`]
+    /// and [`Matching::unmatched_women`].
+    pub fn find_stable_marriage(&mut self) -> Matching {
+        self.find_stable_marriage_observed(&mut ())
+    }
+
+    /// Same as [`find_stable_marriage`](Self::find_stable_marriage), and tells the observer about every step
+    pub fn find_stable_marriage_observed(
+        &mut self,
+        observer: &mut impl EngagementObserver,
+    ) -> Matching {
+        while self.next_engagement_round_observed(observer).is_some() {}
+        self.current_matching()
+    }
+
+    /// Makes every free man propose at once to the best woman he has not proposed to yet.
+    /// Each woman keeps the best of her offers and her current partner, and the other men are free for the next round.
+    /// Returns the number of proposals made, which is 0 once a stable state is reached.
+    pub fn next_synchronous_round(&mut self) -> usize {
+        let free_men: Vec<Man> = std::iter::from_fn(|| self.free_men.pop()).collect();
+        // men that have been rejected by every woman are not put back, they stay single
+        let proposals: Vec<(Man, Woman)> = free_men
+            .into_iter()
+            .filter_map(|m| Some((m, self.take_best_woman_for(m)?)))
+            .collect();
+        for &(m, w) in &proposals {
+            let accepted = match self.women_engagement[w] {
+                Some(m2) => self.woman_prefers(w, m, m2),
+                None => self.woman_accepts(w, m),
+            };
+            let rejected = if accepted {
+                self.women_engagement[w].replace(m)
+            } else {
+                Some(m)
+            };
+            if let Some(rejected) = rejected {
+                self.free_men.push(rejected);
+            }
         }
-        self.women_engagement
+        proposals.len()
+    }
+
+    /// Returns the final stable marriage, computed with [`next_synchronous_round`](Self::next_synchronous_round),
+    /// and the number of rounds in which proposals were made.
+    /// The matching is the same as the one [`find_stable_marriage`](Self::find_stable_marriage) returns.
+    pub fn find_stable_marriage_in_rounds(&mut self) -> (Matching, usize) {
+        let mut rounds = 0;
+        while self.next_synchronous_round() > 0 {
+            rounds += 1;
+        }
+        (self.current_matching(), rounds)
+    }
+
+    /// Same as [`find_stable_marriage`](Self::find_stable_marriage), with the free men proposing concurrently on `threads` threads.
+    /// Each woman's engagement is updated with an atomic compare-and-swap,
+    /// and the result is the same matching, whatever order the proposals happen in.
+    pub fn find_stable_marriage_parallel(&mut self, threads: usize) -> Matching {
+        assert!(threads > 0, "at least one thread is needed");
+        let free_men: Vec<Man> = std::iter::from_fn(|| self.free_men.pop()).collect();
+        let engagement: Vec<AtomicUsize> = self
+            .women_engagement
             .iter()
-            .enumerate()
-            .map(|(w, option_m)| (option_m.unwrap(), w))
+            .map(|m| AtomicUsize::new(m.unwrap_or(NOBODY)))
+            .collect();
+        let next_proposal: Vec<AtomicUsize> = self
+            .next_proposal
+            .iter()
+            .map(|&i| AtomicUsize::new(i))
+            .collect();
+        let proposals = AtomicUsize::new(0);
+        let this = &*self;
+        std::thread::scope(|scope| {
+            for men in free_men.chunks(free_men.len().div_ceil(threads).max(1)) {
+                let (engagement, next_proposal, proposals) =
+                    (&engagement, &next_proposal, &proposals);
+                scope.spawn(move || {
+                    let n = this.propose_concurrently(men, engagement, next_proposal);
+                    proposals.fetch_add(n, Ordering::Relaxed);
+                });
+            }
+        });
+        for (w, m) in engagement.into_iter().enumerate() {
+            self.women_engagement[w] = Some(m.into_inner()).filter(|&m| m != NOBODY);
+        }
+        for (m, i) in next_proposal.into_iter().enumerate() {
+            self.next_proposal[m] = i.into_inner();
+        }
+        self.proposals += proposals.into_inner();
+        self.current_matching()
+    }
+
+    /// Makes the given free men propose until they are engaged or rejected by everyone,
+    /// together with the men they displace. Returns the number of proposals made.
+    /// A man is only handled by one thread at a time: the one that freed him.
+    fn propose_concurrently(
+        &self,
+        men: &[Man],
+        engagement: &[AtomicUsize],
+        next_proposal: &[AtomicUsize],
+    ) -> usize {
+        let mut proposals = 0;
+        for &first in men {
+            let mut m = first;
+            'free: loop {
+                let i = next_proposal[m].load(Ordering::Relaxed);
+                let Some(&w) = self.men_preferences[m].get(i) else {
+                    break; // m has been rejected by every woman, he stays single
+                };
+                next_proposal[m].store(i + 1, Ordering::Relaxed);
+                proposals += 1;
+                if !self.woman_accepts(w, m) {
+                    continue;
+                }
+                let mut current = engagement[w].load(Ordering::Acquire);
+                loop {
+                    if current != NOBODY && self.woman_prefers(w, current, m) {
+                        continue 'free;
+                    }
+                    // the acquire ordering makes the cursor of the displaced man visible to this thread
+                    match engagement[w].compare_exchange_weak(
+                        current,
+                        m,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    ) {
+                        Ok(NOBODY) => break 'free,
+                        Ok(displaced) => {
+                            m = displaced;
+                            continue 'free;
+                        }
+                        Err(actual) => current = actual,
+                    }
+                }
+            }
+        }
+        proposals
+    }
+
+    /// The current engagements, as (man, woman) pairs
+    fn current_matching(&self) -> Matching {
+        Matching::new(
+            self.num_men(),
+            self.num_women(),
+            self.women_engagement
+                .iter()
+                .enumerate()
+                .filter_map(|(w, option_m)| Some(oriented(self.proposing, ((*option_m)?, w)))),
+        )
+    }
+
+    /// The side that makes the proposals
+    pub fn proposing(&self) -> Side {
+        self.proposing
+    }
+
+    /// Men that are not currently engaged. Once the problem is solved, these are the men that stay single.
+    pub fn unmatched_men(&self) -> Vec<Man> {
+        match self.proposing {
+            Side::Men => self.unengaged_proposers(),
+            Side::Women => self.unengaged_receivers(),
+        }
+    }
+
+    /// Women that are not currently engaged. Once the problem is solved, these are the women that stay single.
+    pub fn unmatched_women(&self) -> Vec<Woman> {
+        match self.proposing {
+            Side::Men => self.unengaged_receivers(),
+            Side::Women => self.unengaged_proposers(),
+        }
+    }
+
+    fn unengaged_proposers(&self) -> Vec<Man> {
+        let mut engaged = vec![false; self.men_preferences.len()];
+        for m in self.women_engagement.iter().flatten() {
+            engaged[*m] = true;
+        }
+        (0..engaged.len()).filter(|&m| !engaged[m]).collect()
+    }
+
+    fn unengaged_receivers(&self) -> Vec<Woman> {
+        (0..self.women_engagement.len())
+            .filter(|&w| self.women_engagement[w].is_none())
+            .collect()
     }
 
     /// Whether m and w have a stable marriage in the solution that would be returned by find_stable_marriage
     /// This is faster than calling find_stable_marriage and checking if the result contains (m, w)
     pub fn has_stable_mariage_with(&mut self, man: Man, woman: Woman) -> bool {
+        let (man, woman) = oriented(self.proposing, (man, woman));
         let mut was_engaged = self.women_engagement[woman] == Some(man);
         while let Some((_m, _w)) = self.next_engagement_round() {
             if self.women_engagement[woman] == Some(man) {
@@ -124,38 +524,205 @@ impl GaleShapley {
         was_engaged
     }
 
-    /// Reconstitute a matrix such that `men_rank_matrix[m][w]` is the rank of w in m's preferences
+    /// Solves the problem, and returns an iterator over all of its stable matchings.
+    /// The first one is the matching returned by [`find_stable_marriage`](Self::find_stable_marriage),
+    /// the others are found lazily by eliminating rotations.
+    pub fn all_stable_marriages(self) -> StableMarriages {
+        StableMarriages::new(RotationPoset::new(self))
+    }
+
+    /// Returns a matrix such that `men_rank_matrix[m][w]` is the rank of w in m's preferences,
+    /// or [`UNACCEPTABLE`] if m does not list w
     pub fn men_rank_matrix(&self) -> Vec<Vec<usize>> {
-        let n = self.size();
-        self.men_preferences
-            .iter()
-            .map(|line| {
-                assert_eq!(line.len(), n, "problem already solved, preferences lost");
-                let mut rank = vec![0; line.len()];
-                for (i, w) in line.iter().enumerate() {
-                    rank[*w] = n - i - 1;
-                }
-                rank
-            })
-            .collect()
+        make_partial_rank_matrix(self.men_preferences.clone(), self.women_engagement.len())
+    }
+
+    /// The preferences of m, most preferred first, as given when the problem was created
+    pub fn man_preferences(&self, m: Man) -> &[Woman] {
+        &self.men_preferences[m]
+    }
+
+    /// Number of proposals made so far
+    pub fn proposals(&self) -> usize {
+        self.proposals
     }
 
-    /// `women_preferences[w][m]` is the rank of m in w's preferences
+    /// Cancels all the engagements and proposals, to solve the problem again from the start
+    pub fn reset(&mut self) {
+        self.free_men = FreeMen::new(self.free_men.order().clone(), self.men_preferences.len());
+        self.next_proposal.fill(0);
+        self.women_engagement.fill(None);
+        self.proposals = 0;
+    }
+
+    /// Saves the progress of the algorithm, to come back to it later with [`restore`](Self::restore)
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            free_men: self.free_men.clone(),
+            next_proposal: self.next_proposal.clone(),
+            women_engagement: self.women_engagement.clone(),
+            proposals: self.proposals,
+        }
+    }
+
+    /// Goes back to the progress saved in the checkpoint, cancelling the proposals made since.
+    /// The checkpoint must come from this instance, and its preferences must not have changed since it was taken.
+    /// Panics if the checkpoint does not match the preferences.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        check_progress(
+            &self.men_preferences,
+            &self.women_preferences,
+            &checkpoint.free_men,
+            &checkpoint.next_proposal,
+            &checkpoint.women_engagement,
+        )
+        .unwrap_or_else(|e| panic!("the checkpoint does not match this instance: {e}"));
+        self.free_men.clone_from(&checkpoint.free_men);
+        self.next_proposal.clone_from(&checkpoint.next_proposal);
+        self.women_engagement
+            .clone_from(&checkpoint.women_engagement);
+        self.proposals = checkpoint.proposals;
+    }
+
+    /// `women_preferences[w][m]` is the rank of m in w's preferences, or [`UNACCEPTABLE`] if w does not list m
     pub fn women_preferences(&self) -> &Vec<Vec<usize>> {
         &self.women_preferences
     }
 
-    /// Number of men and women
+    /// Number of agents on the larger side (n for an instance with n men and n women)
     pub fn size(&self) -> usize {
-        self.men_preferences.len()
+        self.num_men().max(self.num_women())
+    }
+
+    /// Number of men
+    pub fn num_men(&self) -> usize {
+        match self.proposing {
+            Side::Men => self.men_preferences.len(),
+            Side::Women => self.women_engagement.len(),
+        }
+    }
+
+    /// Number of women
+    pub fn num_women(&self) -> usize {
+        match self.proposing {
+            Side::Men => self.women_engagement.len(),
+            Side::Women => self.men_preferences.len(),
+        }
+    }
+}
+
+/// Marks a woman that is not engaged in the atomic engagements of the parallel solver
+const NOBODY: Man = usize::MAX;
+
+/// The progress of a [`GaleShapley`] instance, saved by [`checkpoint`](GaleShapley::checkpoint).
+/// It does not contain the preferences, so it is cheaper to make than a clone of the instance.
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "serialization::RawCheckpoint")
+)]
+pub struct Checkpoint {
+    free_men: FreeMen,
+    next_proposal: Vec<usize>,
+    women_engagement: Vec<Option<Man>>,
+    proposals: usize,
+}
+
+/// Checks that every man is engaged at most once, or waiting to propose at most once, but not both,
+/// and that the engaged men have made a proposal
+fn check_engagements(
+    free_men: &FreeMen,
+    next_proposal: &[usize],
+    women_engagement: &[Option<Man>],
+) -> Result<(), String> {
+    let mut seen = vec![false; next_proposal.len()];
+    for m in women_engagement
+        .iter()
+        .flatten()
+        .copied()
+        .chain(free_men.iter())
+    {
+        if m >= seen.len() {
+            return Err(format!("man {m} does not exist"));
+        }
+        if std::mem::replace(&mut seen[m], true) {
+            return Err(format!("man {m} is engaged or free several times"));
+        }
+    }
+    match women_engagement
+        .iter()
+        .flatten()
+        .find(|&&m| next_proposal[m] == 0)
+    {
+        Some(m) => Err(format!("man {m} is engaged without having proposed")),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the progress of the algorithm is consistent with the preferences:
+/// on top of [`check_engagements`], each man has not proposed past the end of his list,
+/// the men that are neither engaged nor free have proposed to every woman on their list,
+/// and the engaged men are engaged to the last woman they proposed to, who accepts them
+fn check_progress(
+    men_preferences: &[Vec<Woman>],
+    women_preferences: &[Vec<usize>],
+    free_men: &FreeMen,
+    next_proposal: &[usize],
+    women_engagement: &[Option<Man>],
+) -> Result<(), String> {
+    if next_proposal.len() != men_preferences.len() {
+        return Err(format!(
+            "expected {} proposal positions",
+            men_preferences.len()
+        ));
+    }
+    if women_engagement.len() != women_preferences.len() {
+        return Err(format!("expected {} engagements", women_preferences.len()));
+    }
+    check_engagements(free_men, next_proposal, women_engagement)?;
+    if let Some(m) =
+        (0..men_preferences.len()).find(|&m| next_proposal[m] > men_preferences[m].len())
+    {
+        return Err(format!("man {m} proposed past the end of his list"));
+    }
+    let mut busy = vec![false; men_preferences.len()];
+    for m in women_engagement
+        .iter()
+        .flatten()
+        .copied()
+        .chain(free_men.iter())
+    {
+        busy[m] = true;
+    }
+    if let Some(m) = (0..men_preferences.len())
+        .find(|&m| !busy[m] && next_proposal[m] < men_preferences[m].len())
+    {
+        return Err(format!(
+            "man {m} is neither engaged nor free, but can still propose"
+        ));
+    }
+    for (w, &m) in women_engagement.iter().enumerate() {
+        let Some(m) = m else { continue };
+        if men_preferences[m][next_proposal[m] - 1] != w || women_preferences[w][m] == UNACCEPTABLE
+        {
+            return Err(format!("man {m} cannot be engaged to woman {w}"));
+        }
+    }
+    Ok(())
+}
+
+/// Converts a (proposer, receiver) couple to a (man, woman) couple, and conversely
+fn oriented<T>(proposing: Side, (a, b): (T, T)) -> (T, T) {
+    match proposing {
+        Side::Men => (a, b),
+        Side::Women => (b, a),
     }
 }
 
 /// men_preferences[m][N-i] is the ith prefered woman of m
 fn make_men_preferences(mut p: Vec<Vec<Woman>>) -> Vec<Vec<Woman>> {
-    let len = p.len();
     for line in &mut p {
-        assert_eq!(line.len(), len);
         line.reverse()
     }
     p
@@ -163,11 +730,14 @@ fn make_men_preferences(mut p: Vec<Vec<Woman>>) -> Vec<Vec<Woman>> {
 
 /// Takes a preference matrix and returns a rank matrix
 /// takes a matrix M where M[w][i] is the man at rank i in w's preferences
-/// and returns the T such as T[w][m] is the rank of m in w's preferences
+/// and returns the T such as T[w][m] is the rank of m in w's preferences.
+/// All lines must be permutations of the same length, but the matrix does not need to be square.
 pub fn make_rank_matrix(mut p: Vec<Vec<Man>>) -> Vec<Vec<usize>> {
-    let len = p.len();
+    let len = p.first().map_or(0, Vec::len);
+    if let Err((row, kind)) = check_lengths(&p, len).and_then(|()| check_preferences(&p, len)) {
+        panic!("invalid preference list {row}: {kind}");
+    }
     for line in &mut p {
-        assert_eq!(line.len(), len);
         for (idx, m) in line.clone().iter().enumerate() {
             line[*m] = idx;
         }
@@ -175,6 +745,45 @@ pub fn make_rank_matrix(mut p: Vec<Vec<Man>>) -> Vec<Vec<usize>> {
     p
 }
 
+/// Same as [`make_rank_matrix`], for incomplete preference lists over `len` agents.
+/// Agents missing from a line get the rank [`UNACCEPTABLE`].
+fn make_partial_rank_matrix(p: Vec<Vec<Man>>, len: usize) -> Vec<Vec<usize>> {
+    p.into_iter()
+        .map(|line| {
+            let mut ranks = vec![UNACCEPTABLE; len];
+            for (idx, m) in line.into_iter().enumerate() {
+                ranks[m] = idx;
+            }
+            ranks
+        })
+        .collect()
+}
+
+/// Takes a rank matrix with ties and returns strict preference lists, most preferred first
+fn break_ties(
+    ranks: Vec<Vec<usize>>,
+    tie_break: TieBreak,
+    rng: &mut impl rand::Rng,
+) -> Vec<Vec<usize>> {
+    ranks
+        .into_iter()
+        .map(|line| {
+            let tie_keys: Vec<usize> = match tie_break {
+                TieBreak::LowestIndex => (0..line.len()).collect(),
+                TieBreak::HighestIndex => (0..line.len()).rev().collect(),
+                TieBreak::Random(_) => {
+                    rand::seq::index::sample(rng, line.len(), line.len()).into_vec()
+                }
+            };
+            let mut prefs: Vec<usize> = (0..line.len())
+                .filter(|&i| line[i] != UNACCEPTABLE)
+                .collect();
+            prefs.sort_by_key(|&i| (line[i], tie_keys[i]));
+            prefs
+        })
+        .collect()
+}
+
 fn rand_pref_matrix(n: usize) -> Vec<Vec<usize>> {
     let mut rng = rand::thread_rng();
     (0..n)
@@ -182,6 +791,8 @@ fn rand_pref_matrix(n: usize) -> Vec<Vec<usize>> {
         .collect()
 }
 
+/// How often the men and the women got each of their choices, over the problems given to [`add_problem`](Stats::add_problem)
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stats {
     // Number of times men got their Nth choice
     pub men: Vec<AtomicUsize>,
@@ -190,6 +801,7 @@ pub struct Stats {
 }
 
 impl Stats {
+    /// `size` must be at least the [size](GaleShapley::size) of the problems that will be added
     pub fn new(size: usize) -> Self {
         Self {
             men: (0..size).map(|_| AtomicUsize::new(0)).collect(),
@@ -197,14 +809,18 @@ impl Stats {
         }
     }
     pub fn add_problem(&self, mut pb: GaleShapley) {
-        let men_preference_ranks = pb.men_rank_matrix();
-        let _ = pb.find_stable_marriage();
-        for w in 0..pb.size() {
-            let m = pb
-                .current_woman_engagement(w)
-                .expect("problem should be solved");
-            let m_rank = pb.women_preferences()[w][m];
-            let w_rank = men_preference_ranks[m][w];
+        let proposers_ranks = pb.men_rank_matrix();
+        let matching = pb.find_stable_marriage();
+        for (m, w) in &matching {
+            // the rank matrices are indexed by proposers and receivers, whatever side proposes
+            let (proposer, receiver) = oriented(pb.proposing(), (m, w));
+            let (w_rank, m_rank) = oriented(
+                pb.proposing(),
+                (
+                    proposers_ranks[proposer][receiver],
+                    pb.women_preferences()[receiver][proposer],
+                ),
+            );
             self.women[m_rank].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             self.men[w_rank].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         }
@@ -214,12 +830,14 @@ impl Stats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::random_ranks;
 
     #[test]
     fn test_find_stable_marriage_1x1() {
         assert_eq!(
             GaleShapley::init(vec![vec![0]], vec![vec![0]])
                 .find_stable_marriage()
+                .iter()
                 .collect::<Vec<_>>(),
             vec![(0, 0)]
         );
@@ -231,6 +849,7 @@ mod tests {
         let expected = vec![(1, 0), (0, 1)]; // The most preferred man ends up with the most preferred woman
         let actual: Vec<(Man, Woman)> = GaleShapley::init(men_preferences, women_preferences)
             .find_stable_marriage()
+            .iter()
             .collect();
         assert_eq!(actual, expected);
     }
@@ -242,10 +861,279 @@ mod tests {
         let expected = vec![(0, 0), (2, 1), (1, 2)];
         let actual: Vec<(Man, Woman)> = GaleShapley::init(men_preferences, women_preferences)
             .find_stable_marriage()
+            .iter()
             .collect();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_find_stable_marriage_more_men() {
+        let men_preferences = vec![vec![0, 1], vec![0, 1], vec![1, 0]];
+        let women_preferences = vec![vec![2, 1, 0], vec![0, 2, 1]];
+        let mut pb = GaleShapley::init(men_preferences, women_preferences);
+        let actual: Vec<(Man, Woman)> = pb.find_stable_marriage().iter().collect();
+        assert_eq!(actual, vec![(2, 0), (0, 1)]);
+        assert_eq!(pb.unmatched_men(), vec![1]);
+        assert!(pb.unmatched_women().is_empty());
+    }
+
+    #[test]
+    fn test_find_stable_marriage_more_women() {
+        let men_preferences = vec![vec![2, 0, 1], vec![2, 1, 0]];
+        let women_preferences = vec![vec![0, 1], vec![0, 1], vec![1, 0]];
+        let mut pb = GaleShapley::init(men_preferences, women_preferences);
+        let actual: Vec<(Man, Woman)> = pb.find_stable_marriage().iter().collect();
+        assert_eq!(actual, vec![(0, 0), (1, 2)]);
+        assert!(pb.unmatched_men().is_empty());
+        assert_eq!(pb.unmatched_women(), vec![1]);
+    }
+
+    #[test]
+    fn test_find_stable_marriage_incomplete_lists() {
+        // man 1 only accepts woman 0, who prefers man 0; woman 1 does not accept man 2
+        let men_preferences = vec![vec![0, 1], vec![0], vec![1]];
+        let women_preferences = vec![vec![0, 1], vec![0, 1]];
+        let mut pb = GaleShapley::init(men_preferences, women_preferences);
+        let actual: Vec<(Man, Woman)> = pb.find_stable_marriage().iter().collect();
+        assert_eq!(actual, vec![(0, 0)]);
+        assert_eq!(pb.unmatched_men(), vec![1, 2]);
+        assert_eq!(pb.unmatched_women(), vec![1]);
+    }
+
+    #[test]
+    fn test_men_rank_matrix_incomplete_lists() {
+        let pb = GaleShapley::init(vec![vec![1], vec![]], vec![vec![0], vec![1, 0]]);
+        assert_eq!(
+            pb.men_rank_matrix(),
+            [[UNACCEPTABLE, 0], [UNACCEPTABLE, UNACCEPTABLE]]
+        );
+        assert_eq!(pb.women_preferences(), &[vec![0, UNACCEPTABLE], vec![1, 0]]);
+    }
+
+    #[test]
+    fn test_preferences_after_solving() {
+        let men_preferences = vec![vec![0, 1], vec![0, 1]];
+        let women_preferences = vec![vec![1, 0], vec![1, 0]];
+        let mut pb = GaleShapley::init(men_preferences, women_preferences);
+        let matching = pb.find_stable_marriage();
+        assert_eq!(pb.proposals(), 3);
+        assert_eq!(pb.men_rank_matrix(), [[0, 1], [0, 1]]);
+        assert_eq!(pb.man_preferences(0), [0, 1]);
+        assert_eq!(pb.best_woman_for(0), None);
+
+        pb.reset();
+        assert_eq!(pb.proposals(), 0);
+        assert_eq!(pb.current_woman_engagement(0), None);
+        assert_eq!(pb.best_woman_for(0), Some(0));
+        assert_eq!(pb.find_stable_marriage(), matching);
+    }
+
+    #[test]
+    fn test_proposal_order() {
+        let men_preferences = || vec![vec![0, 1, 2], vec![0, 2, 1], vec![1, 0, 2]];
+        let women_preferences = || vec![vec![2, 1, 0], vec![0, 1, 2], vec![1, 0, 2]];
+        let expected =
+            GaleShapley::init(men_preferences(), women_preferences()).find_stable_marriage();
+        let orders = [
+            ProposalOrder::Queue,
+            ProposalOrder::Random(7),
+            ProposalOrder::Priority(vec![2, 1, 0]),
+        ];
+        for order in orders {
+            let mut pb = GaleShapley::init(men_preferences(), women_preferences());
+            pb.set_proposal_order(order.clone());
+            let mut first = vec![];
+            while let Some((m, _)) = pb.next_engagement_round() {
+                first.push(m);
+            }
+            assert_eq!(pb.find_stable_marriage(), expected, "{order:?}");
+            if order == ProposalOrder::Priority(vec![2, 1, 0]) {
+                assert_eq!(first[0], 2);
+            }
+        }
+    }
+
+    #[test]
+    fn test_synchronous_rounds() {
+        let mut pb = GaleShapley::init(vec![vec![0, 1, 2]; 3], vec![vec![0, 1, 2]; 3]);
+        assert_eq!(pb.next_synchronous_round(), 3);
+        assert_eq!(pb.current_woman_engagement(0), Some(0));
+        let (matching, rounds) = pb.find_stable_marriage_in_rounds();
+        assert_eq!(rounds, 2);
+        assert_eq!(pb.proposals(), 6);
+        assert_eq!(
+            matching.iter().collect::<Vec<_>>(),
+            vec![(0, 0), (1, 1), (2, 2)]
+        );
+
+        for _ in 0..20 {
+            let mut pb = GaleShapley::init_random(10);
+            let (matching, rounds) = pb.find_stable_marriage_in_rounds();
+            assert!(rounds <= pb.proposals());
+            pb.reset();
+            assert_eq!(matching, pb.find_stable_marriage());
+        }
+    }
+
+    #[test]
+    fn test_parallel() {
+        for threads in [1, 4] {
+            for _ in 0..10 {
+                let mut pb = GaleShapley::init_random(200);
+                let matching = pb.find_stable_marriage_parallel(threads);
+                assert_eq!(matching.len(), 200);
+                pb.reset();
+                assert_eq!(matching, pb.find_stable_marriage());
+            }
+        }
+        let men_preferences = vec![vec![0, 1], vec![0], vec![1, 0]];
+        let women_preferences = vec![vec![1, 2], vec![0, 2]];
+        let expected = GaleShapley::init_with_proposing(
+            men_preferences.clone(),
+            women_preferences.clone(),
+            Side::Women,
+        )
+        .find_stable_marriage();
+        let mut pb =
+            GaleShapley::init_with_proposing(men_preferences, women_preferences, Side::Women);
+        assert_eq!(pb.find_stable_marriage_parallel(3), expected);
+        assert_eq!(pb.next_engagement_round(), None);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let mut pb = GaleShapley::init_random(20);
+        pb.next_engagement_round();
+        let checkpoint = pb.checkpoint();
+        let mut copy = pb.clone();
+        // peeking ahead solves the problem
+        let w = pb.best_woman_for(1).unwrap();
+        let has_stable_marriage = pb.has_stable_mariage_with(1, w);
+        pb.restore(&checkpoint);
+        assert_eq!(pb.proposals(), 1);
+        let matching = pb.find_stable_marriage();
+        assert_eq!(matching.contains((1, w)), has_stable_marriage);
+        assert_eq!(copy.find_stable_marriage(), matching);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut pb = GaleShapley::init_random(10);
+        pb.set_proposal_order(ProposalOrder::Random(3));
+        for _ in 0..5 {
+            pb.next_engagement_round();
+        }
+        let json = serde_json::to_string(&pb).unwrap();
+        let mut resumed: GaleShapley = serde_json::from_str(&json).unwrap();
+        assert_eq!(resumed.proposals(), 5);
+        while let Some(proposal) = pb.next_engagement_round() {
+            assert_eq!(resumed.next_engagement_round(), Some(proposal));
+        }
+        let matching = resumed.find_stable_marriage();
+        assert_eq!(pb.find_stable_marriage(), matching);
+        let json = serde_json::to_string(&matching).unwrap();
+        assert_eq!(serde_json::from_str::<Matching>(&json).unwrap(), matching);
+
+        let stats = Stats::new(10);
+        stats.add_problem(pb);
+        let json = serde_json::to_string(&stats).unwrap();
+        let deserialized: Stats = serde_json::from_str(&json).unwrap();
+        let counts = |stats: &Stats| -> Vec<usize> {
+            stats
+                .men
+                .iter()
+                .chain(&stats.women)
+                .map(|count| count.load(std::sync::atomic::Ordering::Relaxed))
+                .collect()
+        };
+        assert_eq!(counts(&deserialized), counts(&stats));
+        assert_eq!(counts(&stats).iter().sum::<usize>(), 20);
+    }
+
+    #[test]
+    fn test_find_stable_marriage_with_ties() {
+        let men_ranks = || vec![vec![0, 0], vec![0, 1]]; // man 0 is indifferent
+        let women_ranks = || vec![vec![0, 0], vec![0, 0]]; // both women are indifferent
+        let solve = |tie_break| -> Vec<(Man, Woman)> {
+            GaleShapley::init_with_ties(men_ranks(), women_ranks(), tie_break)
+                .find_stable_marriage()
+                .iter()
+                .collect()
+        };
+        assert_eq!(solve(TieBreak::LowestIndex), vec![(0, 0), (1, 1)]);
+        assert_eq!(solve(TieBreak::HighestIndex), vec![(1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn test_random_tie_break_reproducible() {
+        let pb =
+            GaleShapley::init_with_ties(vec![vec![0; 4]], vec![vec![0]; 4], TieBreak::Random(7));
+        // ChaCha8 gives the same numbers for the same seed in every version of rand_chacha
+        assert_eq!(pb.man_preferences(0), [3, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_ties_weakly_stable() {
+        let n = 8;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        for seed in 0..20 {
+            let men_ranks = random_ranks(&mut rng, n, n, 3, 1.0);
+            let women_ranks = random_ranks(&mut rng, n, n, 3, 1.0);
+            let mut pb = GaleShapley::init_with_ties(
+                men_ranks.clone(),
+                women_ranks.clone(),
+                TieBreak::Random(seed),
+            );
+            let pairs: Vec<(Man, Woman)> = pb.find_stable_marriage().iter().collect();
+            assert_eq!(pairs.len(), n);
+            let mut wife = vec![0; n];
+            for &(m, w) in &pairs {
+                wife[m] = w;
+            }
+            for &(m2, w) in &pairs {
+                for m in 0..n {
+                    let strictly_blocking = men_ranks[m][w] < men_ranks[m][wife[m]]
+                        && women_ranks[w][m] < women_ranks[w][m2];
+                    assert!(!strictly_blocking, "({m}, {w}) is a blocking pair");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_stable_marriage_women_proposing() {
+        let men_preferences = || vec![vec![0, 1], vec![1, 0]];
+        let women_preferences = || vec![vec![1, 0], vec![0, 1]];
+        let solve = |proposing| -> Vec<(Man, Woman)> {
+            GaleShapley::init_with_proposing(men_preferences(), women_preferences(), proposing)
+                .find_stable_marriage()
+                .iter()
+                .collect()
+        };
+        assert_eq!(solve(Side::Men), vec![(0, 0), (1, 1)]); // man-optimal
+        assert_eq!(solve(Side::Women), vec![(1, 0), (0, 1)]); // woman-optimal
+    }
+
+    #[test]
+    fn test_women_proposing_unequal() {
+        let men_preferences = || vec![vec![0, 1], vec![0], vec![1]];
+        let women_preferences = || vec![vec![1, 0, 2], vec![0, 2]];
+        let init = || {
+            GaleShapley::init_with_proposing(men_preferences(), women_preferences(), Side::Women)
+        };
+        let mut pb = init();
+        assert_eq!((pb.num_men(), pb.num_women()), (3, 2));
+        let matching = pb.find_stable_marriage();
+        assert_eq!(matching.iter().collect::<Vec<_>>(), vec![(1, 0), (0, 1)]);
+        assert_eq!(matching.partner_of_man(0), Some(1));
+        assert_eq!(matching.unmatched_men(), vec![2]);
+        assert_eq!(pb.unmatched_men(), vec![2]);
+        assert!(pb.unmatched_women().is_empty());
+        assert!(init().has_stable_mariage_with(1, 0));
+        assert!(!init().has_stable_mariage_with(2, 1));
+    }
+
     #[test]
     fn test_make_women_preferences() {
         assert_eq!(
@@ -254,12 +1142,69 @@ mod tests {
         )
     }
 
+    #[test]
+    #[should_panic(expected = "invalid preference list 1: 0 appears twice")]
+    fn test_make_rank_matrix_duplicates() {
+        make_rank_matrix(vec![vec![0, 1], vec![0, 0]]);
+    }
+
+    #[test]
+    fn test_try_init() {
+        assert!(GaleShapley::try_init(vec![vec![0]], vec![vec![0]]).is_ok());
+        let error = GaleShapley::try_init(vec![vec![0], vec![1]], vec![vec![0, 1]]).err();
+        assert_eq!(
+            error,
+            Some(PreferenceError {
+                side: Side::Men,
+                row: 1,
+                kind: PreferenceErrorKind::OutOfRange { agent: 1, len: 1 }
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid preference list 1: expected 2 entries, found 1")]
+    fn test_make_rank_matrix_uneven_rows() {
+        make_rank_matrix(vec![vec![0, 1], vec![1]]);
+    }
+
+    #[test]
+    fn test_try_init_with_proposing_and_ties() {
+        let error =
+            GaleShapley::try_init_with_proposing(vec![vec![0]], vec![vec![0, 0]], Side::Women)
+                .err()
+                .unwrap();
+        assert_eq!((error.side, error.row), (Side::Women, 0));
+        assert_eq!(error.kind, PreferenceErrorKind::Duplicate(0));
+        let error = GaleShapley::try_init_with_ties(
+            vec![vec![0, 0], vec![0]],
+            vec![vec![0, 0], vec![0, 0]],
+            TieBreak::LowestIndex,
+        )
+        .err()
+        .unwrap();
+        assert_eq!((error.side, error.row), (Side::Men, 1));
+        assert_eq!(
+            error.kind,
+            PreferenceErrorKind::WrongLength {
+                expected: 2,
+                found: 1
+            }
+        );
+        assert!(GaleShapley::try_init_with_ties(
+            vec![vec![0]],
+            vec![vec![0]],
+            TieBreak::LowestIndex
+        )
+        .is_ok());
+    }
+
     #[test]
     fn test_rand() {
         let n = 100;
         let mut men = std::collections::HashSet::new();
         let mut women = std::collections::HashSet::new();
-        for (m, w) in GaleShapley::init_random(n).find_stable_marriage() {
+        for (m, w) in &GaleShapley::init_random(n).find_stable_marriage() {
             men.insert(m);
             women.insert(w);
         }
@@ -301,4 +1246,20 @@ mod tests {
         assert_eq!(stats.women[0].load(o), 1);
         assert_eq!(stats.women[1].load(o), 1);
     }
+
+    #[test]
+    fn stats_women_proposing() {
+        let men_preferences = vec![vec![0, 1], vec![1, 0]];
+        let women_preferences = vec![vec![1, 0], vec![0, 1]];
+        let stats = Stats::new(2);
+        stats.add_problem(GaleShapley::init_with_proposing(
+            men_preferences,
+            women_preferences,
+            Side::Women,
+        ));
+        let o = std::sync::atomic::Ordering::Acquire;
+        // both women get their first choice, and both men their second
+        assert_eq!(stats.men[1].load(o), 2);
+        assert_eq!(stats.women[0].load(o), 2);
+    }
 }
@@ -1,4 +1,4 @@
-use galeshapley::{GaleShapley, Man, Stats, Woman};
+use galeshapley::{GaleShapley, Man, Matching, Stats, Woman};
 
 use std::{
     collections::{HashMap, HashSet},
@@ -101,7 +101,7 @@ fn invert_map(h: HashMap<String, usize>) -> Vec<String> {
 fn run_from_parsed_stdin_problem() {
     let p = parse_input(std::io::stdin().lock());
     let mut algo: GaleShapley = GaleShapley::init(p.men_preferences, p.women_preferences);
-    for (man, woman) in algo.find_stable_marriage() {
+    for (man, woman) in &algo.find_stable_marriage() {
         println!("{}: {}", p.men_names[man], p.women_names[woman]);
     }
 }
@@ -112,7 +112,7 @@ fn run_random(n: usize) {
     let mut got_first_choice = 0;
     for total_tries in 1.. {
         let mut pb = GaleShapley::init_random(n);
-        let preferred_woman = pb.best_woman_for(0);
+        let preferred_woman = pb.best_woman_for(0).expect("random problems are not empty");
         got_first_choice += pb.has_stable_mariage_with(0, preferred_woman) as usize;
         let rate = got_first_choice as f64 / total_tries as f64;
         let confidence = 100. * 1.96 * (rate * (1. - rate) / total_tries as f64).sqrt();
@@ -184,8 +184,11 @@ fn print_all_problems(size: usize) {
                 print!("{},", r.women_preferences[i][j] + 1);
             }
         }
-        for (i, &(m, w)) in r.mariages.iter().enumerate() {
-            assert_eq!(i, w);
+        for w in 0..size {
+            let m = r
+                .mariages
+                .partner_of_woman(w)
+                .expect("everybody is married");
             print!("{},", m + 1);
         }
         println!();
@@ -195,7 +198,7 @@ fn print_all_problems(size: usize) {
 struct GaleResult {
     men_preferences: Vec<Vec<usize>>,
     women_preferences: Vec<Vec<usize>>,
-    mariages: Vec<(usize, usize)>,
+    mariages: Matching,
 }
 
 impl From<(Vec<Vec<usize>>, Vec<Vec<usize>>)> for GaleResult {
@@ -203,9 +206,7 @@ impl From<(Vec<Vec<usize>>, Vec<Vec<usize>>)> for GaleResult {
         Self {
             men_preferences: pb.0.clone(),
             women_preferences: pb.1.clone(),
-            mariages: GaleShapley::init(pb.0, pb.1)
-                .find_stable_marriage()
-                .collect(),
+            mariages: GaleShapley::init(pb.0, pb.1).find_stable_marriage(),
         }
     }
 }
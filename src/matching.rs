@@ -0,0 +1,247 @@
+//! The result of the algorithms: who is married to whom.
+
+use crate::{Man, Woman};
+
+/// A matching between men and women, that can be looked up from both sides
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawMatching")
+)]
+pub struct Matching {
+    /// wives[m] is the woman m is married to
+    wives: Vec<Option<Woman>>,
+
+    /// husbands[w] is the man w is married to
+    husbands: Vec<Option<Man>>,
+}
+
+impl Matching {
+    /// A matching between `num_men` men and `num_women` women, made of the given pairs.
+    /// Panics if somebody is in several pairs.
+    pub fn new(
+        num_men: usize,
+        num_women: usize,
+        pairs: impl IntoIterator<Item = (Man, Woman)>,
+    ) -> Matching {
+        let mut matching = Matching {
+            wives: vec![None; num_men],
+            husbands: vec![None; num_women],
+        };
+        for (m, w) in pairs {
+            assert!(
+                matching.wives[m].is_none() && matching.husbands[w].is_none(),
+                "({m}, {w}) is not the only pair of {m} or {w}"
+            );
+            matching.wives[m] = Some(w);
+            matching.husbands[w] = Some(m);
+        }
+        matching
+    }
+
+    /// The woman m is married to
+    pub fn partner_of_man(&self, m: Man) -> Option<Woman> {
+        self.wives[m]
+    }
+
+    /// The man w is married to
+    pub fn partner_of_woman(&self, w: Woman) -> Option<Man> {
+        self.husbands[w]
+    }
+
+    /// Number of pairs
+    pub fn len(&self) -> usize {
+        self.husbands.iter().flatten().count()
+    }
+
+    /// Whether nobody is married
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The (man, woman) pairs, in the order of the women
+    pub fn iter(&self) -> Pairs<'_> {
+        Pairs(self.husbands.iter().enumerate())
+    }
+
+    /// Men that are not married
+    pub fn unmatched_men(&self) -> Vec<Man> {
+        (0..self.wives.len())
+            .filter(|&m| self.wives[m].is_none())
+            .collect()
+    }
+
+    /// Women that are not married
+    pub fn unmatched_women(&self) -> Vec<Woman> {
+        (0..self.husbands.len())
+            .filter(|&w| self.husbands[w].is_none())
+            .collect()
+    }
+
+    /// Sum of the ranks the men give to their wives, where `men_ranks[m][w]` is the rank of w in m's preferences,
+    /// as returned by [`men_rank_matrix`](crate::GaleShapley::men_rank_matrix)
+    pub fn men_cost(&self, men_ranks: &[Vec<usize>]) -> usize {
+        self.iter().map(|(m, w)| men_ranks[m][w]).sum()
+    }
+
+    /// Sum of the ranks the women give to their husbands, where `women_ranks[w][m]` is the rank of m in w's preferences,
+    /// as returned by [`women_preferences`](crate::GaleShapley::women_preferences)
+    pub fn women_cost(&self, women_ranks: &[Vec<usize>]) -> usize {
+        self.iter().map(|(m, w)| women_ranks[w][m]).sum()
+    }
+
+    /// Worst rank anybody gives to their partner
+    pub fn regret(&self, men_ranks: &[Vec<usize>], women_ranks: &[Vec<usize>]) -> usize {
+        self.iter()
+            .map(|(m, w)| men_ranks[m][w].max(women_ranks[w][m]))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The pairs that are in this matching but not in `new`, and the pairs that are in `new` but not in this one
+    pub fn changes_to(&self, new: &Matching) -> MatchingChanges {
+        MatchingChanges {
+            dissolved: self.iter().filter(|&pair| !new.contains(pair)).collect(),
+            formed: new.iter().filter(|&pair| !self.contains(pair)).collect(),
+        }
+    }
+
+    /// Whether m and w are married. They do not need to be part of the matching.
+    pub fn contains(&self, (m, w): (Man, Woman)) -> bool {
+        self.wives.get(m) == Some(&Some(w))
+    }
+}
+
+/// The couples that changed between two matchings, in the order of the women
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchingChanges {
+    /// Pairs that were in the old matching and are not in the new one
+    pub dissolved: Vec<(Man, Woman)>,
+    /// Pairs that are in the new matching and were not in the old one
+    pub formed: Vec<(Man, Woman)>,
+}
+
+impl MatchingChanges {
+    /// Whether the two matchings are the same
+    pub fn is_empty(&self) -> bool {
+        self.dissolved.is_empty() && self.formed.is_empty()
+    }
+}
+
+/// A [`Matching`] as it is serialized, before checking that both sides agree
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawMatching {
+    wives: Vec<Option<Woman>>,
+    husbands: Vec<Option<Man>>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawMatching> for Matching {
+    type Error = String;
+
+    fn try_from(raw: RawMatching) -> Result<Matching, String> {
+        let married =
+            |partners: &[Option<usize>], a: usize, b: usize| partners.get(a) == Some(&Some(b));
+        for (m, w) in raw.wives.iter().enumerate() {
+            match w {
+                Some(w) if !married(&raw.husbands, *w, m) => {
+                    return Err(format!("woman {w} is not married to man {m}"))
+                }
+                _ => {}
+            }
+        }
+        for (w, m) in raw.husbands.iter().enumerate() {
+            match m {
+                Some(m) if !married(&raw.wives, *m, w) => {
+                    return Err(format!("man {m} is not married to woman {w}"))
+                }
+                _ => {}
+            }
+        }
+        Ok(Matching {
+            wives: raw.wives,
+            husbands: raw.husbands,
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a Matching {
+    type Item = (Man, Woman);
+    type IntoIter = Pairs<'a>;
+
+    fn into_iter(self) -> Pairs<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the pairs of a [`Matching`]
+pub struct Pairs<'a>(std::iter::Enumerate<std::slice::Iter<'a, Option<Man>>>);
+
+impl Iterator for Pairs<'_> {
+    type Item = (Man, Woman);
+
+    fn next(&mut self) -> Option<(Man, Woman)> {
+        self.0.find_map(|(w, m)| Some(((*m)?, w)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookups() {
+        let matching = Matching::new(3, 2, [(2, 0), (0, 1)]);
+        assert_eq!(matching.partner_of_man(0), Some(1));
+        assert_eq!(matching.partner_of_man(1), None);
+        assert_eq!(matching.partner_of_woman(0), Some(2));
+        assert_eq!(matching.len(), 2);
+        assert_eq!(matching.iter().collect::<Vec<_>>(), vec![(2, 0), (0, 1)]);
+        assert_eq!(matching.unmatched_men(), vec![1]);
+        assert!(matching.unmatched_women().is_empty());
+    }
+
+    #[test]
+    fn test_costs() {
+        let men_ranks = vec![vec![0, 1], vec![1, 0]];
+        let women_ranks = vec![vec![1, 0], vec![0, 1]];
+        let matching = Matching::new(2, 2, [(0, 0), (1, 1)]);
+        assert_eq!(matching.men_cost(&men_ranks), 0);
+        assert_eq!(matching.women_cost(&women_ranks), 2);
+        assert_eq!(matching.regret(&men_ranks, &women_ranks), 1);
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = Matching::new(3, 3, [(0, 0), (1, 1), (2, 2)]);
+        let new = Matching::new(3, 3, [(0, 0), (2, 1), (1, 2)]);
+        let changes = old.changes_to(&new);
+        assert_eq!(changes.dissolved, vec![(1, 1), (2, 2)]);
+        assert_eq!(changes.formed, vec![(2, 1), (1, 2)]);
+        assert!(old.changes_to(&old).is_empty());
+        let more_men = Matching::new(4, 3, [(3, 0), (1, 1), (2, 2)]);
+        assert_eq!(old.changes_to(&more_men).formed, vec![(3, 0)]);
+        assert_eq!(more_men.changes_to(&old).dissolved, vec![(3, 0)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize() {
+        let matching = Matching::new(3, 2, [(2, 0), (0, 1)]);
+        let json = serde_json::to_string(&matching).unwrap();
+        assert_eq!(serde_json::from_str::<Matching>(&json).unwrap(), matching);
+        let corrupted = r#"{"wives":[1,null],"husbands":[1,null]}"#;
+        assert!(serde_json::from_str::<Matching>(corrupted).is_err());
+        let corrupted = r#"{"wives":[5],"husbands":[null]}"#;
+        assert!(serde_json::from_str::<Matching>(corrupted).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_married_twice() {
+        Matching::new(2, 2, [(0, 0), (1, 0)]);
+    }
+}
@@ -0,0 +1,102 @@
+//! Hooks to follow the Gale-Shapley algorithm as it runs.
+
+use crate::{Man, Woman};
+
+/// Receives the events of [`GaleShapley`](crate::GaleShapley) as they happen,
+/// through [`next_engagement_round_observed`](crate::GaleShapley::next_engagement_round_observed)
+/// and [`find_stable_marriage_observed`](crate::GaleShapley::find_stable_marriage_observed).
+///
+/// Each proposal is followed either by a rejection, or by an acceptance,
+/// which comes right after the divorce of the woman if she was already engaged.
+/// Like [`next_engagement_round`](crate::GaleShapley::next_engagement_round), the events call the proposers men.
+/// All the methods do nothing by default.
+///
+/// These two methods are the only ones that report events. The other solvers
+/// ([`next_engagement_round`](crate::GaleShapley::next_engagement_round),
+/// [`find_stable_marriage`](crate::GaleShapley::find_stable_marriage),
+/// [`next_synchronous_round`](crate::GaleShapley::next_synchronous_round),
+/// [`find_stable_marriage_in_rounds`](crate::GaleShapley::find_stable_marriage_in_rounds)
+/// and [`find_stable_marriage_parallel`](crate::GaleShapley::find_stable_marriage_parallel))
+/// make their proposals silently, and so do the updates of
+/// [`update_preferences`](crate::GaleShapley::update_preferences), [`add_man`](crate::GaleShapley::add_man),
+/// [`add_woman`](crate::GaleShapley::add_woman), [`remove_man`](crate::GaleShapley::remove_man)
+/// and [`remove_woman`](crate::GaleShapley::remove_woman), which also cancel proposals without telling anybody.
+/// An observer attached after one of them only sees the proposals made from then on.
+pub trait EngagementObserver {
+    /// m proposes to w
+    fn proposal(&mut self, _m: Man, _w: Woman) {}
+
+    /// w accepts the proposal of m, and they are now engaged
+    fn acceptance(&mut self, _m: Man, _w: Woman) {}
+
+    /// w rejects the proposal of m
+    fn rejection(&mut self, _m: Man, _w: Woman) {}
+
+    /// w breaks her engagement with m, to accept a man she prefers
+    fn divorce(&mut self, _m: Man, _w: Woman) {}
+}
+
+/// Ignores all the events
+impl EngagementObserver for () {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GaleShapley;
+
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl EngagementObserver for Log {
+        fn proposal(&mut self, m: Man, w: Woman) {
+            self.0.push(format!("{m} proposes to {w}"));
+        }
+        fn acceptance(&mut self, m: Man, w: Woman) {
+            self.0.push(format!("{w} accepts {m}"));
+        }
+        fn rejection(&mut self, m: Man, w: Woman) {
+            self.0.push(format!("{w} rejects {m}"));
+        }
+        fn divorce(&mut self, m: Man, w: Woman) {
+            self.0.push(format!("{w} leaves {m}"));
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let men_preferences = vec![vec![0, 1], vec![0, 1]];
+        let women_preferences = vec![vec![1, 0], vec![0]];
+        let mut pb = GaleShapley::init(men_preferences, women_preferences);
+        let mut log = Log::default();
+        let matching = pb.find_stable_marriage_observed(&mut log);
+        assert_eq!(matching.iter().collect::<Vec<_>>(), vec![(1, 0), (0, 1)]);
+        assert_eq!(
+            log.0,
+            [
+                "0 proposes to 0",
+                "0 accepts 0",
+                "1 proposes to 0",
+                "0 leaves 0",
+                "0 accepts 1",
+                "0 proposes to 1",
+                "1 accepts 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer_rejection() {
+        let mut pb = GaleShapley::init(vec![vec![0], vec![0]], vec![vec![0]]);
+        let mut log = Log::default();
+        while pb.next_engagement_round_observed(&mut log).is_some() {}
+        assert_eq!(
+            log.0,
+            [
+                "0 proposes to 0",
+                "0 accepts 0",
+                "1 proposes to 0",
+                "0 rejects 1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Stable matchings that are the best for some criterion, among all the stable matchings of an instance.
+//!
+//! They are found in the [`RotationPoset`]: each closed set of rotations gives one stable matching,
+//! and eliminating a rotation changes the ranks of the partners of its men and women by a fixed amount.
+
+use crate::{GaleShapley, Matching, RotationPoset};
+use std::collections::VecDeque;
+
+/// How to compare the fairness of stable matchings, from the sum of the ranks the men give to their partners
+/// and the sum of the ranks the women give to theirs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fairness {
+    /// Minimize the difference between the two sums
+    SexEqual,
+    /// Minimize the larger of the two sums
+    Balanced,
+}
+
+impl Fairness {
+    /// The quantity to minimize
+    fn cost(self, (men, women): (isize, isize)) -> isize {
+        match self {
+            Fairness::SexEqual => (men - women).abs(),
+            Fairness::Balanced => men.max(women),
+        }
+    }
+}
+
+impl GaleShapley {
+    /// Solves the problem and returns the egalitarian stable matching, which minimizes the sum of the ranks
+    /// every man and every woman give to their partner, as in [`men_rank_matrix`](Self::men_rank_matrix)
+    /// and [`women_preferences`](Self::women_preferences).
+    pub fn egalitarian_stable_marriage(self) -> Matching {
+        let poset = RotationPoset::new(self);
+        poset.matching(&poset.egalitarian())
+    }
+
+    /// Solves the problem and returns a minimum regret stable matching: among all the stable matchings,
+    /// one in which the worst rank anybody gives to their partner is as small as possible.
+    pub fn minimum_regret_stable_marriage(self) -> Matching {
+        let poset = RotationPoset::new(self);
+        poset.matching(&poset.minimum_regret())
+    }
+
+    /// Solves the problem and returns the fairest stable matching for the given criterion.
+    /// All the stable matchings are compared, and there can be exponentially many of them,
+    /// so this is only practical for small instances; see [`approximately_fair_stable_marriage`](Self::approximately_fair_stable_marriage).
+    pub fn fair_stable_marriage(self, criterion: Fairness) -> Matching {
+        let poset = RotationPoset::new(self);
+        poset.matching(&poset.fairest(criterion))
+    }
+
+    /// Solves the problem and returns a stable matching that is fair for the given criterion, in polynomial time,
+    /// with the heuristic of [`RotationPoset::approximately_fairest`].
+    pub fn approximately_fair_stable_marriage(self, criterion: Fairness) -> Matching {
+        let poset = RotationPoset::new(self);
+        poset.matching(&poset.approximately_fairest(criterion))
+    }
+}
+
+impl RotationPoset {
+    /// The closed set of rotations to eliminate to get the egalitarian stable matching
+    pub fn egalitarian(&self) -> Vec<bool> {
+        let weights: Vec<isize> = (0..self.len())
+            .map(|r| {
+                let (men, women) = self.rank_changes(r);
+                men + women
+            })
+            .collect();
+        self.min_weight_closed_set(&weights)
+    }
+
+    /// The closed set of rotations to eliminate to get a minimum regret stable matching
+    pub fn minimum_regret(&self) -> Vec<bool> {
+        // the proposers can only get worse partners, so their ranks are a lower bound
+        let lower_bound = self.initial_pairs().map(|pair| self.ranks(pair).0).max();
+        (lower_bound.unwrap_or(0)..)
+            .find_map(|k| self.regret_at_most(k))
+            .expect("every stable matching has a regret below the length of the longest list")
+    }
+
+    /// The closed set of rotations to eliminate to get the fairest stable matching for the given criterion,
+    /// found by trying them all
+    pub fn fairest(&self, criterion: Fairness) -> Vec<bool> {
+        let changes: Vec<(isize, isize)> = (0..self.len()).map(|r| self.rank_changes(r)).collect();
+        let cost = |eliminated: &[bool]| {
+            let costs = (0..self.len())
+                .filter(|&r| eliminated[r])
+                .fold(self.initial_costs(), |(men, women), r| {
+                    (men + changes[r].0, women + changes[r].1)
+                });
+            criterion.cost(costs)
+        };
+        let mut eliminated = vec![false; self.len()];
+        let mut best = (cost(&eliminated), eliminated.clone());
+        while self.next_closed_set(&mut eliminated) {
+            let c = cost(&eliminated);
+            if c < best.0 {
+                best = (c, eliminated.clone());
+            }
+        }
+        best.1
+    }
+
+    /// A closed set of rotations that gives a fair stable matching for the given criterion.
+    ///
+    /// This heuristic goes from the man-optimal matching to the woman-optimal one,
+    /// always eliminating the exposed rotation that gives the fairest next matching,
+    /// and returns the fairest matching on the way. It takes a time quadratic in the number of rotations,
+    /// but can miss the optimum when it is off this path.
+    pub fn approximately_fairest(&self, criterion: Fairness) -> Vec<bool> {
+        let mut eliminated = vec![false; self.len()];
+        let mut costs = self.initial_costs();
+        let mut best = (criterion.cost(costs), eliminated.clone());
+        let exposed =
+            |eliminated: &[bool], r: usize| !eliminated[r] && self.is_exposed(r, eliminated);
+        let after = |(men, women): (isize, isize), r: usize| {
+            let (m, w) = self.rank_changes(r);
+            (men + m, women + w)
+        };
+        while let Some(r) = (0..self.len())
+            .filter(|&r| exposed(&eliminated, r))
+            .min_by_key(|&r| criterion.cost(after(costs, r)))
+        {
+            eliminated[r] = true;
+            costs = after(costs, r);
+            if criterion.cost(costs) < best.0 {
+                best = (criterion.cost(costs), eliminated.clone());
+            }
+        }
+        best.1
+    }
+
+    /// The smallest closed set of rotations that gives a stable matching in which nobody gets a partner
+    /// they rank below k, if there is one
+    fn regret_at_most(&self, k: usize) -> Option<Vec<bool>> {
+        // the rotations that give a proposer a partner ranked below k, and the ones that come after them
+        let mut forbidden = vec![false; self.len()];
+        for r in 0..self.len() {
+            forbidden[r] = self.formed_pairs(r).any(|pair| self.ranks(pair).0 > k)
+                || self.predecessors(r).iter().any(|&r2| forbidden[r2]);
+        }
+        // each receiver must be given a partner ranked k or better by the first rotation that does it,
+        // since rotations are numbered in the order in which they improve the receivers' partners
+        let mut eliminated = vec![false; self.len()];
+        for (_, w) in self.initial_pairs().filter(|&pair| self.ranks(pair).1 > k) {
+            let r = (0..self.len()).find(|&r| {
+                self.formed_pairs(r)
+                    .any(|(m, w2)| w2 == w && self.ranks((m, w)).1 <= k)
+            })?;
+            eliminated[r] = true;
+        }
+        for r in (0..self.len()).rev() {
+            if eliminated[r] {
+                for &r2 in self.predecessors(r) {
+                    eliminated[r2] = true;
+                }
+            }
+        }
+        let feasible = (0..self.len()).all(|r| !(eliminated[r] && forbidden[r]));
+        feasible.then_some(eliminated)
+    }
+
+    /// A closed set of rotations with the minimum total weight, given by a minimum cut:
+    /// rotations that lower the total are linked to the source, the others to the sink,
+    /// and each rotation to its predecessors with an infinite capacity, so that the source side is closed.
+    fn min_weight_closed_set(&self, weights: &[isize]) -> Vec<bool> {
+        let (source, sink) = (self.len(), self.len() + 1);
+        let infinite = weights.iter().map(|w| w.unsigned_abs()).sum::<usize>() + 1;
+        let mut network = FlowNetwork::new(self.len() + 2);
+        for (r, &weight) in weights.iter().enumerate() {
+            if weight < 0 {
+                network.add_edge(source, r, weight.unsigned_abs());
+            } else if weight > 0 {
+                network.add_edge(r, sink, weight.unsigned_abs());
+            }
+        }
+        for (r, r2) in self.edges() {
+            network.add_edge(r2, r, infinite);
+        }
+        let mut reachable = network.min_cut(source, sink);
+        reachable.truncate(self.len());
+        reachable
+    }
+}
+
+/// A flow network on which the maximum flow is computed with the Edmonds-Karp algorithm
+struct FlowNetwork {
+    /// edges[e] is the (destination, residual capacity) of edge e. The reverse of edge e is edge e ^ 1.
+    edges: Vec<(usize, usize)>,
+
+    /// adjacency[v] are the edges leaving v
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl FlowNetwork {
+    fn new(size: usize) -> Self {
+        FlowNetwork {
+            edges: vec![],
+            adjacency: vec![vec![]; size],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: usize) {
+        self.adjacency[from].push(self.edges.len());
+        self.edges.push((to, capacity));
+        self.adjacency[to].push(self.edges.len());
+        self.edges.push((from, 0));
+    }
+
+    /// Breadth-first search in the residual network. parent_edges[v] is the edge used to reach v
+    fn residual_search(&self, source: usize) -> Vec<Option<usize>> {
+        let mut parent_edges = vec![None; self.adjacency.len()];
+        let mut visited = vec![false; self.adjacency.len()];
+        visited[source] = true;
+        let mut queue = VecDeque::from([source]);
+        while let Some(v) = queue.pop_front() {
+            for &e in &self.adjacency[v] {
+                let (to, capacity) = self.edges[e];
+                if capacity > 0 && !visited[to] {
+                    visited[to] = true;
+                    parent_edges[to] = Some(e);
+                    queue.push_back(to);
+                }
+            }
+        }
+        parent_edges
+    }
+
+    /// Saturates the network, and returns the vertices that are on the source side of a minimum cut
+    fn min_cut(&mut self, source: usize, sink: usize) -> Vec<bool> {
+        loop {
+            let parent_edges = self.residual_search(source);
+            if parent_edges[sink].is_none() {
+                return (0..self.adjacency.len())
+                    .map(|v| v == source || parent_edges[v].is_some())
+                    .collect();
+            }
+            let path: Vec<usize> =
+                std::iter::successors(parent_edges[sink], |&e| parent_edges[self.edges[e ^ 1].0])
+                    .collect();
+            let flow = path.iter().map(|&e| self.edges[e].1).min().unwrap();
+            for e in path {
+                self.edges[e].1 -= flow;
+                self.edges[e ^ 1].1 += flow;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::random_lists;
+    use crate::*;
+    use rand::{Rng, SeedableRng};
+
+    fn pairs(matching: Matching) -> Vec<(Man, Woman)> {
+        matching.iter().collect()
+    }
+
+    #[test]
+    fn test_egalitarian() {
+        let men = vec![vec![0, 2, 1], vec![2, 0, 1], vec![1, 2, 0]];
+        let women = vec![vec![2, 1, 0], vec![1, 2, 0], vec![0, 2, 1]];
+        // the man-optimal and the woman-optimal matchings both cost 5, the one in between costs 4
+        let actual = GaleShapley::init(men, women).egalitarian_stable_marriage();
+        assert_eq!(pairs(actual), vec![(1, 0), (2, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn test_minimum_regret() {
+        let men = vec![vec![0, 2, 1], vec![2, 0, 1], vec![1, 2, 0]];
+        let women = vec![vec![2, 1, 0], vec![1, 2, 0], vec![0, 2, 1]];
+        // somebody gets their last choice in the man-optimal and in the woman-optimal matchings
+        let actual = GaleShapley::init(men, women).minimum_regret_stable_marriage();
+        assert_eq!(pairs(actual), vec![(1, 0), (2, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn test_fair() {
+        let men = vec![vec![0, 2, 1], vec![2, 0, 1], vec![1, 2, 0]];
+        let women = vec![vec![2, 1, 0], vec![1, 2, 0], vec![0, 2, 1]];
+        // the men's and women's costs are 0 and 5, 2 and 2, then 5 and 0
+        for criterion in [Fairness::SexEqual, Fairness::Balanced] {
+            let pb = GaleShapley::init(men.clone(), women.clone());
+            let actual = pb.fair_stable_marriage(criterion);
+            assert_eq!(pairs(actual), vec![(1, 0), (2, 1), (0, 2)]);
+            let pb = GaleShapley::init(men.clone(), women.clone());
+            let actual = pb.approximately_fair_stable_marriage(criterion);
+            assert_eq!(pairs(actual), vec![(1, 0), (2, 1), (0, 2)]);
+        }
+    }
+
+    #[test]
+    fn test_optimal_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..300 {
+            let n = rng.gen_range(1..7);
+            let men = random_lists(&mut rng, n, n, true);
+            let women = random_lists(&mut rng, n, n, true);
+            let men_ranks = make_rank_matrix(men.clone());
+            let women_ranks = make_rank_matrix(women.clone());
+            let all: Vec<Matching> = GaleShapley::init(men.clone(), women.clone())
+                .all_stable_marriages()
+                .collect();
+
+            let cost = |matching: &Matching| {
+                matching.men_cost(&men_ranks) + matching.women_cost(&women_ranks)
+            };
+            let best = all.iter().map(cost).min();
+            let egalitarian =
+                GaleShapley::init(men.clone(), women.clone()).egalitarian_stable_marriage();
+            assert_eq!(Some(cost(&egalitarian)), best);
+
+            let regret = |matching: &Matching| matching.regret(&men_ranks, &women_ranks);
+            let best = all.iter().map(regret).min();
+            for proposing in [Side::Men, Side::Women] {
+                let minimum_regret =
+                    GaleShapley::init_with_proposing(men.clone(), women.clone(), proposing)
+                        .minimum_regret_stable_marriage();
+                assert_eq!(Some(regret(&minimum_regret)), best);
+            }
+
+            for criterion in [Fairness::SexEqual, Fairness::Balanced] {
+                let cost = |matching: &Matching| {
+                    let men_cost = matching.men_cost(&men_ranks) as isize;
+                    criterion.cost((men_cost, matching.women_cost(&women_ranks) as isize))
+                };
+                let best = all.iter().map(cost).min();
+                let pb = GaleShapley::init(men.clone(), women.clone());
+                assert_eq!(Some(cost(&pb.fair_stable_marriage(criterion))), best);
+                let pb = GaleShapley::init(men.clone(), women.clone());
+                let approximate = pb.approximately_fair_stable_marriage(criterion);
+                assert!(all.contains(&approximate));
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! The order in which the free men propose.
+//!
+//! It changes the sequence of proposals, but never the final matching.
+
+use crate::Man;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+
+/// In which order the free men make their proposals
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProposalOrder {
+    /// The man that became free last proposes first
+    #[default]
+    Stack,
+    /// The men propose in the order in which they became free
+    Queue,
+    /// The next man to propose is chosen at random, deterministically from the given seed
+    Random(u64),
+    /// The free man with the lowest priority proposes first, where `priorities[m]` is the priority of m.
+    /// Among men with the same priority, the one with the lowest index proposes first,
+    /// and the men added after the order was chosen propose last.
+    Priority(Vec<usize>),
+}
+
+/// The men that can still propose, ordered according to a [`ProposalOrder`]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct FreeMen {
+    order: ProposalOrder,
+
+    /// The free men, the next one to propose being the last one
+    men: VecDeque<Man>,
+
+    /// Used to place the men in the queue when the order is random
+    rng: ChaCha8Rng,
+}
+
+impl FreeMen {
+    /// All the men are free at first
+    pub(crate) fn new(order: ProposalOrder, num_men: usize) -> Self {
+        let mut free_men = FreeMen {
+            rng: rng_for(&order),
+            order,
+            men: (0..num_men).rev().collect(),
+        };
+        free_men.rearrange();
+        free_men
+    }
+
+    pub(crate) fn order(&self) -> &ProposalOrder {
+        &self.order
+    }
+
+    /// Changes the order of the men that are currently free, and of the ones that will be freed
+    pub(crate) fn set_order(&mut self, order: ProposalOrder) {
+        self.rng = rng_for(&order);
+        self.order = order;
+        self.rearrange();
+    }
+
+    /// Places the free men again according to the order. Men with a smaller index propose first
+    /// when they were placed in decreasing order, by a stack or by a queue.
+    fn rearrange(&mut self) {
+        if matches!(
+            self.order,
+            ProposalOrder::Random(_) | ProposalOrder::Priority(_)
+        ) {
+            for m in std::mem::take(&mut self.men) {
+                self.push(m);
+            }
+        }
+    }
+
+    /// The man that proposes next
+    pub(crate) fn next(&self) -> Option<Man> {
+        self.men.back().copied()
+    }
+
+    /// Removes the man that proposes next
+    pub(crate) fn pop(&mut self) -> Option<Man> {
+        self.men.pop_back()
+    }
+
+    /// The men waiting to propose, in no particular order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Man> + '_ {
+        self.men.iter().copied()
+    }
+
+    /// Adds a man that became free
+    pub(crate) fn push(&mut self, m: Man) {
+        match &self.order {
+            ProposalOrder::Stack => self.men.push_back(m),
+            ProposalOrder::Queue => self.men.push_front(m),
+            ProposalOrder::Random(_) => {
+                let position = self.rng.gen_range(0..=self.men.len());
+                self.men.insert(position, m);
+            }
+            ProposalOrder::Priority(priorities) => {
+                let key = |m: Man| (priorities.get(m).copied().unwrap_or(usize::MAX), m);
+                let position = self.men.partition_point(|&m2| key(m2) > key(m));
+                self.men.insert(position, m);
+            }
+        }
+    }
+}
+
+fn rng_for(order: &ProposalOrder) -> ChaCha8Rng {
+    let seed = match order {
+        ProposalOrder::Random(seed) => *seed,
+        _ => 0,
+    };
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal_order(free_men: &mut FreeMen) -> Vec<Man> {
+        std::iter::from_fn(|| free_men.pop()).collect()
+    }
+
+    #[test]
+    fn test_orders() {
+        let mut stack = FreeMen::new(ProposalOrder::Stack, 3);
+        assert_eq!(stack.pop(), Some(0));
+        assert_eq!(stack.pop(), Some(1));
+        stack.push(0);
+        assert_eq!(proposal_order(&mut stack), [0, 2]);
+
+        let mut queue = FreeMen::new(ProposalOrder::Queue, 3);
+        assert_eq!(queue.pop(), Some(0));
+        queue.push(0);
+        assert_eq!(proposal_order(&mut queue), [1, 2, 0]);
+
+        let mut priority = FreeMen::new(ProposalOrder::Priority(vec![2, 0, 1, 0]), 4);
+        assert_eq!(proposal_order(&mut priority), [1, 3, 2, 0]);
+
+        let mut random = proposal_order(&mut FreeMen::new(ProposalOrder::Random(1), 10));
+        let again = proposal_order(&mut FreeMen::new(ProposalOrder::Random(1), 10));
+        assert_eq!(random, again);
+        random.sort_unstable();
+        assert_eq!(random, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,383 @@
+//! Stable roommates: pairing people from a single pool, with Irving's algorithm.
+
+use crate::{check_preferences, UNACCEPTABLE};
+
+pub type Person = usize;
+
+/// Proof that an instance has no stable matching: an odd party of a stable partition.
+///
+/// The persons form a cycle of odd length, in which each person prefers the next one to the previous one,
+/// and no two persons of the instance would rather be together than with their predecessors in the partition.
+/// Every stable partition of the instance contains this odd cycle, which rules out any stable matching.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OddParty(pub Vec<Person>);
+
+/// The preference table, from which pairs are deleted as the algorithm progresses
+struct Table {
+    /// preferences[x] is the original list of x, most preferred first
+    preferences: Vec<Vec<Person>>,
+    /// ranks[x][y] is the rank of y in x's list, or UNACCEPTABLE if y was deleted from x's list
+    ranks: Vec<Vec<usize>>,
+    /// the entries of preferences[x] before first[x] are all deleted
+    first: Vec<usize>,
+    /// the entries of preferences[x] from last[x] on are all deleted
+    last: Vec<usize>,
+}
+
+impl Table {
+    fn new(preferences: Vec<Vec<Person>>) -> Self {
+        let n = preferences.len();
+        let mut ranks = vec![vec![UNACCEPTABLE; n]; n];
+        for (x, line) in preferences.iter().enumerate() {
+            for (i, &y) in line.iter().enumerate() {
+                ranks[x][y] = i;
+            }
+        }
+        // a pair is only acceptable if both persons list each other
+        let preferences: Vec<Vec<Person>> = preferences
+            .into_iter()
+            .enumerate()
+            .map(|(x, line)| {
+                line.into_iter()
+                    .filter(|&y| ranks[y][x] != UNACCEPTABLE)
+                    .collect()
+            })
+            .collect();
+        for (x, line) in preferences.iter().enumerate() {
+            ranks[x].fill(UNACCEPTABLE);
+            for (i, &y) in line.iter().enumerate() {
+                ranks[x][y] = i;
+            }
+        }
+        Table {
+            first: vec![0; n],
+            last: preferences.iter().map(Vec::len).collect(),
+            preferences,
+            ranks,
+        }
+    }
+
+    fn is_deleted(&self, x: Person, y: Person) -> bool {
+        self.ranks[x][y] == UNACCEPTABLE
+    }
+
+    /// Removes y from x's list and x from y's list
+    fn delete(&mut self, x: Person, y: Person) {
+        self.ranks[x][y] = UNACCEPTABLE;
+        self.ranks[y][x] = UNACCEPTABLE;
+    }
+
+    /// The entries left in x's list, most preferred first
+    fn list(&self, x: Person) -> impl Iterator<Item = Person> + '_ {
+        self.preferences[x][self.first[x]..self.last[x]]
+            .iter()
+            .copied()
+            .filter(move |&y| !self.is_deleted(x, y))
+    }
+
+    /// The person x likes best among the ones left in his list
+    fn first(&mut self, x: Person) -> Option<Person> {
+        while self.first[x] < self.last[x] {
+            let y = self.preferences[x][self.first[x]];
+            if !self.is_deleted(x, y) {
+                return Some(y);
+            }
+            self.first[x] += 1;
+        }
+        None
+    }
+
+    /// The person x likes second best among the ones left in his list
+    fn second(&mut self, x: Person) -> Option<Person> {
+        self.first(x)?;
+        self.list(x).nth(1)
+    }
+
+    /// The person x likes least among the ones left in his list
+    fn last(&mut self, x: Person) -> Option<Person> {
+        while self.last[x] > self.first[x] {
+            let y = self.preferences[x][self.last[x] - 1];
+            if !self.is_deleted(x, y) {
+                return Some(y);
+            }
+            self.last[x] -= 1;
+        }
+        None
+    }
+
+    /// Deletes from x's list all the persons x likes less than y
+    fn delete_successors(&mut self, x: Person, y: Person) {
+        let successors: Vec<Person> = self
+            .list(x)
+            .filter(|&z| self.ranks[x][z] > self.ranks[x][y])
+            .collect();
+        for z in successors {
+            self.delete(x, z);
+        }
+    }
+
+    /// Phase 1: everybody proposes in turn, and each person holds the best proposal received so far.
+    /// At the end, y is first in x's list if and only if x is last in y's list.
+    fn proposals(&mut self) {
+        let n = self.preferences.len();
+        let mut holds: Vec<Option<Person>> = vec![None; n];
+        let mut free: Vec<Person> = (0..n).rev().collect();
+        while let Some(x) = free.pop() {
+            // x is in the list of y, so y holds nobody better than x
+            let Some(y) = self.first(x) else { continue };
+            let rejected = holds[y].replace(x);
+            self.delete_successors(y, x);
+            free.extend(rejected);
+        }
+    }
+
+    /// Finds a rotation exposed in the table, as the persons x_0 ... x_{r-1}
+    /// such that x_{i+1} is last in the list of second(x_i)
+    fn exposed_rotation(&mut self) -> Option<Vec<Person>> {
+        let n = self.preferences.len();
+        let start = (0..n).find(|&x| self.second(x).is_some())?;
+        let mut position = vec![None; n];
+        let mut sequence = vec![];
+        let mut p = start;
+        while position[p].is_none() {
+            position[p] = Some(sequence.len());
+            sequence.push(p);
+            let q = self
+                .second(p)
+                .expect("internal error: rotation without second choice");
+            p = self
+                .last(q)
+                .expect("internal error: empty list in rotation");
+        }
+        Some(sequence.split_off(position[p].unwrap()))
+    }
+
+    /// A rotation x_0 ... x_{r-1} of odd length r where each x_i is first in the list of x_{i+(r+1)/2}
+    /// cannot be eliminated: its persons form an odd party, in the order of their first choices.
+    fn odd_party(&mut self, rotation: &[Person]) -> Option<OddParty> {
+        let r = rotation.len();
+        if r.is_multiple_of(2) {
+            return None;
+        }
+        let shift = (r - 1) / 2;
+        for i in 0..r {
+            if self.first(rotation[i]) != Some(rotation[(i + shift) % r]) {
+                return None;
+            }
+        }
+        Some(OddParty((0..r).map(|i| rotation[i * shift % r]).collect()))
+    }
+
+    /// Eliminates a rotation: second(x_i) deletes everyone after x_i from its list
+    fn eliminate(&mut self, rotation: &[Person]) {
+        let seconds: Vec<Person> = rotation
+            .iter()
+            .map(|&x| {
+                self.second(x)
+                    .expect("internal error: rotation without second choice")
+            })
+            .collect();
+        for (&x, &y) in rotation.iter().zip(&seconds) {
+            self.delete_successors(y, x);
+        }
+    }
+}
+
+/// Finds a stable matching of the persons in `preferences`, where `preferences[x]` lists the persons
+/// x would accept as a roommate, most preferred first. Persons who end up with nobody are left out of the matching.
+///
+/// Returns the pairs `(x, y)` with `x < y` in increasing order of x,
+/// or an [`OddParty`] proving that no stable matching exists.
+///
+/// Panics if a list contains a person that does not exist, the same person twice, or the person whose list it is.
+pub fn stable_roommates(preferences: Vec<Vec<Person>>) -> Result<Vec<(Person, Person)>, OddParty> {
+    check_roommates(&preferences);
+    let mut table = Table::new(preferences);
+    table.proposals();
+    while let Some(rotation) = table.exposed_rotation() {
+        if let Some(odd_party) = table.odd_party(&rotation) {
+            return Err(odd_party);
+        }
+        table.eliminate(&rotation);
+    }
+    let n = table.preferences.len();
+    Ok((0..n)
+        .filter_map(|x| Some((x, table.first(x)?)))
+        .filter(|&(x, y)| x < y)
+        .collect())
+}
+
+/// Panics with the first invalid list, which the algorithm could loop on or misread
+fn check_roommates(preferences: &[Vec<Person>]) {
+    if let Err((x, kind)) = check_preferences(preferences, preferences.len()) {
+        panic!("invalid preferences for person {x}: {kind}");
+    }
+    if let Some(x) = (0..preferences.len()).find(|&x| preferences[x].contains(&x)) {
+        panic!("invalid preferences for person {x}: {x} lists itself");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_lists;
+    use rand::{Rng, SeedableRng};
+
+    /// Whether x prefers y over his partner, according to ranks
+    fn prefers(ranks: &[Vec<usize>], x: Person, y: Person, partner: Option<Person>) -> bool {
+        ranks[x][y] != UNACCEPTABLE && partner.is_none_or(|p| ranks[x][y] < ranks[x][p])
+    }
+
+    fn is_stable(ranks: &[Vec<usize>], partner: &[Option<Person>]) -> bool {
+        (0..ranks.len()).all(|x| {
+            (0..ranks.len()).all(|y| {
+                x == y
+                    || partner[x] == Some(y)
+                    || !(prefers(ranks, x, y, partner[x]) && prefers(ranks, y, x, partner[y]))
+            })
+        })
+    }
+
+    fn exists_stable(ranks: &[Vec<usize>], partner: &mut Vec<Option<Person>>, x: Person) -> bool {
+        let n = ranks.len();
+        if x == n {
+            return is_stable(ranks, partner);
+        }
+        if partner[x].is_some() {
+            return exists_stable(ranks, partner, x + 1);
+        }
+        if exists_stable(ranks, partner, x + 1) {
+            return true;
+        }
+        for y in x + 1..n {
+            if partner[y].is_none() && ranks[x][y] != UNACCEPTABLE && ranks[y][x] != UNACCEPTABLE {
+                partner[x] = Some(y);
+                partner[y] = Some(x);
+                let found = exists_stable(ranks, partner, x + 1);
+                partner[x] = None;
+                partner[y] = None;
+                if found {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// All the stable partitions, as permutations where successor[x] is x's successor in his cycle
+    fn stable_partitions(ranks: &[Vec<usize>]) -> Vec<Vec<Person>> {
+        fn extend(
+            ranks: &[Vec<usize>],
+            successor: &mut Vec<Person>,
+            used: &mut Vec<bool>,
+            out: &mut Vec<Vec<Person>>,
+        ) {
+            let n = ranks.len();
+            let x = successor.len();
+            if x == n {
+                let mut predecessor = vec![None; n];
+                for (x, &y) in successor.iter().enumerate() {
+                    predecessor[y] = (y != x).then_some(x);
+                }
+                let ordered = (0..n).all(|x| {
+                    predecessor[x].is_none_or(|p| {
+                        p == successor[x] || prefers(ranks, x, successor[x], Some(p))
+                    })
+                });
+                let unblocked = (0..n).all(|x| {
+                    (0..n).all(|y| {
+                        x == y
+                            || !(prefers(ranks, x, y, predecessor[x])
+                                && prefers(ranks, y, x, predecessor[y]))
+                    })
+                });
+                if ordered && unblocked {
+                    out.push(successor.clone());
+                }
+                return;
+            }
+            for y in 0..n {
+                if !used[y] && (y == x || ranks[x][y] != UNACCEPTABLE) {
+                    used[y] = true;
+                    successor.push(y);
+                    extend(ranks, successor, used, out);
+                    successor.pop();
+                    used[y] = false;
+                }
+            }
+        }
+        let mut out = vec![];
+        extend(ranks, &mut vec![], &mut vec![false; ranks.len()], &mut out);
+        out
+    }
+
+    #[test]
+    fn test_stable_roommates() {
+        let preferences = vec![vec![2, 3, 1], vec![2, 3, 0], vec![1, 0, 3], vec![0, 1, 2]];
+        assert_eq!(stable_roommates(preferences), Ok(vec![(0, 3), (1, 2)]));
+    }
+
+    #[test]
+    fn test_no_stable_roommates() {
+        // 0, 1 and 2 each prefer the next one, and nobody wants 3
+        let preferences = vec![vec![1, 2, 3], vec![2, 0, 3], vec![0, 1, 3], vec![0, 1, 2]];
+        assert_eq!(stable_roommates(preferences), Err(OddParty(vec![0, 1, 2])));
+    }
+
+    #[test]
+    fn test_stable_roommates_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            let n = rng.gen_range(1..8);
+            let mut preferences = random_lists(&mut rng, n, n, false);
+            for (x, line) in preferences.iter_mut().enumerate() {
+                line.retain(|&y| y != x);
+            }
+            let ranks = Table::new(preferences.clone()).ranks;
+            match stable_roommates(preferences) {
+                Ok(pairs) => {
+                    let mut partner = vec![None; n];
+                    for (x, y) in pairs {
+                        assert!(partner[x].is_none() && partner[y].is_none());
+                        partner[x] = Some(y);
+                        partner[y] = Some(x);
+                    }
+                    assert!(is_stable(&ranks, &partner));
+                }
+                Err(OddParty(cycle)) => {
+                    assert!(cycle.len() >= 3 && cycle.len() % 2 == 1);
+                    for (i, &x) in cycle.iter().enumerate() {
+                        let next = cycle[(i + 1) % cycle.len()];
+                        let previous = cycle[(i + cycle.len() - 1) % cycle.len()];
+                        assert!(prefers(&ranks, x, next, Some(previous)));
+                    }
+                    assert!(!exists_stable(&ranks, &mut vec![None; n], 0));
+                    for successor in stable_partitions(&ranks) {
+                        assert!(cycle
+                            .iter()
+                            .enumerate()
+                            .all(|(i, &x)| successor[x] == cycle[(i + 1) % cycle.len()]));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid preferences for person 0: 1 appears twice")]
+    fn test_duplicate_roommate() {
+        let _ = stable_roommates(vec![vec![1, 1], vec![0]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid preferences for person 0: 0 lists itself")]
+    fn test_self_roommate() {
+        let _ = stable_roommates(vec![vec![0, 1], vec![0]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid preferences for person 1: 2 is out of range")]
+    fn test_unknown_roommate() {
+        let _ = stable_roommates(vec![vec![1], vec![2, 0]]);
+    }
+}
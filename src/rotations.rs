@@ -0,0 +1,482 @@
+//! Rotations, which describe how to move from one stable matching to the others.
+//!
+//! A rotation is a cyclic sequence of pairs `(m_0, w_0), ..., (m_{r-1}, w_{r-1})` of a stable matching,
+//! in which each man `m_i` leaves `w_i` for `w_{i+1}`, the next woman down his list who would accept him.
+//! Starting from the man-optimal matching, eliminating rotations one after the other reaches every stable matching.
+
+use crate::{oriented, GaleShapley, Man, Matching, Side, Woman, UNACCEPTABLE};
+
+/// The rotations of an instance, and the precedence relation between them.
+///
+/// The stable matchings of the instance are in one-to-one correspondence with the closed subsets of rotations:
+/// the sets that contain the predecessors of each of their rotations.
+/// The rotations are numbered so that each one comes after its predecessors.
+pub struct RotationPoset {
+    /// The side that made the proposals, called men below
+    proposing: Side,
+
+    /// men_preferences[m][i] is the ith prefered woman of m
+    men_preferences: Vec<Vec<Woman>>,
+
+    /// men_ranks[m][w] is the rank of w in m's preferences, or UNACCEPTABLE
+    men_ranks: Vec<Vec<usize>>,
+
+    /// women_ranks[w][m] is the rank of m in w's preferences, or UNACCEPTABLE
+    women_ranks: Vec<Vec<usize>>,
+
+    /// man_optimal[m] is the wife of m in the man-optimal stable matching
+    man_optimal: Vec<Option<Woman>>,
+
+    /// The rotations, as (m_i, w_i) pairs, in an order compatible with their precedence
+    rotations: Vec<Vec<(Man, Woman)>>,
+
+    /// predecessors[r] are the rotations that must be eliminated before rotation r
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl RotationPoset {
+    /// Solves the problem and finds all of its rotations.
+    pub fn new(mut pb: GaleShapley) -> Self {
+        let men_ranks = pb.men_rank_matrix();
+        while pb.next_engagement_round().is_some() {}
+        RotationPoset::from_ranks(
+            pb.proposing,
+            men_ranks,
+            pb.women_preferences,
+            pb.women_engagement,
+        )
+    }
+
+    /// Finds all the rotations, by eliminating them one after the other from the man-optimal matching
+    /// until the woman-optimal matching is reached. `husbands[w]` is the husband of w in the man-optimal matching.
+    fn from_ranks(
+        proposing: Side,
+        men_ranks: Vec<Vec<usize>>,
+        women_ranks: Vec<Vec<usize>>,
+        husbands: Vec<Option<Man>>,
+    ) -> Self {
+        let men_preferences: Vec<Vec<Woman>> = men_ranks
+            .iter()
+            .map(|ranks| {
+                let mut line: Vec<Woman> = (0..ranks.len())
+                    .filter(|&w| ranks[w] != UNACCEPTABLE)
+                    .collect();
+                line.sort_by_key(|&w| ranks[w]);
+                line
+            })
+            .collect();
+        let mut man_optimal = vec![None; men_ranks.len()];
+        for (w, m) in husbands.iter().enumerate() {
+            if let Some(m) = *m {
+                man_optimal[m] = Some(w);
+            }
+        }
+        let mut poset = RotationPoset {
+            proposing,
+            men_preferences,
+            men_ranks,
+            women_ranks,
+            man_optimal,
+            rotations: vec![],
+            predecessors: vec![],
+        };
+        poset.find_rotations(husbands);
+        poset
+    }
+
+    fn find_rotations(&mut self, mut husbands: Vec<Option<Man>>) {
+        let men_ranks = std::mem::take(&mut self.men_ranks);
+        let mut wives = self.man_optimal.clone();
+        // candidates[m] is the position in m's list from which to look for his next woman
+        let mut candidates: Vec<usize> = wives
+            .iter()
+            .enumerate()
+            .map(|(m, w)| w.map_or(self.men_preferences[m].len(), |w| men_ranks[m][w] + 1))
+            .collect();
+        // the rotation that created each pair, and the successive husbands of each woman with the rotation that gave them
+        let mut created_by = std::collections::HashMap::new();
+        let mut women_history: Vec<Vec<(Option<usize>, Man)>> = husbands
+            .iter()
+            .map(|m| m.iter().map(|&m| (None, m)).collect())
+            .collect();
+
+        while let Some(cycle) = self.exposed_rotation(&wives, &husbands, &mut candidates) {
+            let r = self.rotations.len();
+            let rotation: Vec<(Man, Woman)> =
+                cycle.iter().map(|&m| (m, wives[m].unwrap())).collect();
+            let mut predecessors = vec![];
+            for (i, &(m, w)) in rotation.iter().enumerate() {
+                let next_w = rotation[(i + 1) % rotation.len()].1;
+                // the rotation that gave w to m must come first
+                predecessors.extend(created_by.get(&(m, w)).copied());
+                // every woman m skips must already be with a man she prefers to him
+                for &w2 in &self.men_preferences[m][men_ranks[m][w] + 1..men_ranks[m][next_w]] {
+                    let rank = self.women_ranks[w2][m];
+                    if rank == UNACCEPTABLE {
+                        continue;
+                    }
+                    let moved_above = women_history[w2]
+                        .iter()
+                        .find(|&&(_, m2)| self.women_ranks[w2][m2] < rank);
+                    predecessors.extend(moved_above.and_then(|&(r2, _)| r2));
+                }
+            }
+            for (i, &(m, _)) in rotation.iter().enumerate() {
+                let next_w = rotation[(i + 1) % rotation.len()].1;
+                wives[m] = Some(next_w);
+                husbands[next_w] = Some(m);
+                candidates[m] = men_ranks[m][next_w] + 1;
+                created_by.insert((m, next_w), r);
+                women_history[next_w].push((Some(r), m));
+            }
+            predecessors.sort_unstable();
+            predecessors.dedup();
+            self.rotations.push(rotation);
+            self.predecessors.push(predecessors);
+        }
+        self.men_ranks = men_ranks;
+    }
+
+    /// The next woman for m: the first one after his wife in his list who prefers him to her husband.
+    /// If she is single, she stays single in every stable matching, and m can never go past her.
+    fn next_woman(
+        &self,
+        m: Man,
+        husbands: &[Option<Man>],
+        candidates: &mut [usize],
+    ) -> Option<Woman> {
+        let line = &self.men_preferences[m];
+        while let Some(&w) = line.get(candidates[m]) {
+            let ranks = &self.women_ranks[w];
+            if ranks[m] != UNACCEPTABLE {
+                match husbands[w] {
+                    None => return None,
+                    Some(m2) if ranks[m] < ranks[m2] => return Some(w),
+                    Some(_) => {}
+                }
+            }
+            candidates[m] += 1;
+        }
+        None
+    }
+
+    /// Finds a rotation exposed in the given matching, as the list of its men
+    fn exposed_rotation(
+        &self,
+        wives: &[Option<Woman>],
+        husbands: &[Option<Man>],
+        candidates: &mut [usize],
+    ) -> Option<Vec<Man>> {
+        const UNVISITED: usize = usize::MAX;
+        const DEAD_END: usize = usize::MAX - 1;
+        // position[m] is the position of m in the current path
+        let mut position = vec![UNVISITED; wives.len()];
+        for start in 0..wives.len() {
+            let mut path = vec![];
+            let mut m = start;
+            loop {
+                if position[m] == DEAD_END || wives[m].is_none() {
+                    break;
+                }
+                if position[m] != UNVISITED {
+                    return Some(path.split_off(position[m]));
+                }
+                position[m] = path.len();
+                path.push(m);
+                match self.next_woman(m, husbands, candidates) {
+                    Some(w) => m = husbands[w].expect("internal error: next woman is single"),
+                    None => break,
+                }
+            }
+            for m in path {
+                position[m] = DEAD_END;
+            }
+        }
+        None
+    }
+
+    /// Number of rotations
+    pub fn len(&self) -> usize {
+        self.rotations.len()
+    }
+
+    /// Whether the instance has a single stable matching
+    pub fn is_empty(&self) -> bool {
+        self.rotations.is_empty()
+    }
+
+    /// The pairs of rotation r, in cyclic order.
+    /// Eliminating it matches the proposer of each pair with the receiver of the next one:
+    /// with men proposing, each man leaves his wife for the woman of the next pair.
+    pub fn rotation(&self, r: usize) -> Vec<(Man, Woman)> {
+        self.rotations[r]
+            .iter()
+            .map(|&pair| oriented(self.proposing, pair))
+            .collect()
+    }
+
+    /// The rotations that must be eliminated before rotation r, all numbered lower than r.
+    /// The precedence relation is the transitive closure of these edges.
+    pub fn predecessors(&self, r: usize) -> &[usize] {
+        &self.predecessors[r]
+    }
+
+    /// All the precedence edges, as (predecessor, successor) couples
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.predecessors
+            .iter()
+            .enumerate()
+            .flat_map(|(r, predecessors)| predecessors.iter().map(move |&r2| (r2, r)))
+    }
+
+    /// The pairs of the matching found by the proposers, as (proposer, receiver) couples
+    pub(crate) fn initial_pairs(&self) -> impl Iterator<Item = (Man, Woman)> + '_ {
+        self.man_optimal
+            .iter()
+            .enumerate()
+            .filter_map(|(m, w)| Some((m, (*w)?)))
+    }
+
+    /// The pairs formed by eliminating rotation r, as (proposer, receiver) couples
+    pub(crate) fn formed_pairs(&self, r: usize) -> impl Iterator<Item = (Man, Woman)> + '_ {
+        let rotation = &self.rotations[r];
+        (0..rotation.len()).map(|i| (rotation[i].0, rotation[(i + 1) % rotation.len()].1))
+    }
+
+    /// The rank of the receiver in the proposer's preferences, and the rank of the proposer in the receiver's
+    pub(crate) fn ranks(&self, (m, w): (Man, Woman)) -> (usize, usize) {
+        (self.men_ranks[m][w], self.women_ranks[w][m])
+    }
+
+    /// The sum of the ranks of the men's partners, and the sum of the ranks of the women's partners,
+    /// in the matching found by the proposers
+    pub(crate) fn initial_costs(&self) -> (isize, isize) {
+        let (proposers, receivers) = self
+            .initial_pairs()
+            .map(|pair| self.ranks(pair))
+            .fold((0, 0), |(a, b), (c, d)| (a + c as isize, b + d as isize));
+        oriented(self.proposing, (proposers, receivers))
+    }
+
+    /// How much eliminating rotation r changes the sum of the ranks of the men's partners,
+    /// and the sum of the ranks of the women's partners
+    pub(crate) fn rank_changes(&self, r: usize) -> (isize, isize) {
+        let rotation = &self.rotations[r];
+        let (mut proposers, mut receivers) = (0, 0);
+        for (i, &(m, w)) in rotation.iter().enumerate() {
+            let (next_m, next_w) = rotation[(i + 1) % rotation.len()];
+            proposers += self.men_ranks[m][next_w] as isize - self.men_ranks[m][w] as isize;
+            receivers +=
+                self.women_ranks[next_w][m] as isize - self.women_ranks[next_w][next_m] as isize;
+        }
+        oriented(self.proposing, (proposers, receivers))
+    }
+
+    /// Whether all the predecessors of rotation r are in `eliminated`
+    pub(crate) fn is_exposed(&self, r: usize, eliminated: &[bool]) -> bool {
+        self.predecessors[r].iter().all(|&r2| eliminated[r2])
+    }
+
+    /// Whether `eliminated[r]` is true for all the predecessors of the rotations r for which it is true
+    pub fn is_closed(&self, eliminated: &[bool]) -> bool {
+        assert_eq!(
+            eliminated.len(),
+            self.len(),
+            "expected one boolean per rotation"
+        );
+        (0..self.len()).all(|r| !eliminated[r] || self.is_exposed(r, eliminated))
+    }
+
+    /// Replaces a closed set of rotations by the next one in lexicographic order, or returns false if it was the last
+    pub(crate) fn next_closed_set(&self, eliminated: &mut [bool]) -> bool {
+        let next = (0..eliminated.len())
+            .rev()
+            .find(|&r| !eliminated[r] && self.is_exposed(r, eliminated));
+        if let Some(r) = next {
+            eliminated[r] = true;
+            eliminated[r + 1..].fill(false);
+        }
+        next.is_some()
+    }
+
+    /// The stable matching obtained by eliminating the given closed set of rotations
+    /// from the matching returned by [`find_stable_marriage`](GaleShapley::find_stable_marriage),
+    pub fn matching(&self, eliminated: &[bool]) -> Matching {
+        assert!(
+            self.is_closed(eliminated),
+            "the rotations to eliminate are not a closed set"
+        );
+        let mut husbands = vec![None; self.women_ranks.len()];
+        for (m, w) in self.man_optimal.iter().enumerate() {
+            if let Some(w) = *w {
+                husbands[w] = Some(m);
+            }
+        }
+        for (rotation, _) in self.rotations.iter().zip(eliminated).filter(|(_, &e)| e) {
+            for (i, &(m, _)) in rotation.iter().enumerate() {
+                husbands[rotation[(i + 1) % rotation.len()].1] = Some(m);
+            }
+        }
+        let (num_men, num_women) = oriented(
+            self.proposing,
+            (self.men_preferences.len(), self.women_ranks.len()),
+        );
+        let pairs = husbands
+            .into_iter()
+            .enumerate()
+            .filter_map(|(w, m)| Some(oriented(self.proposing, (m?, w))));
+        Matching::new(num_men, num_women, pairs)
+    }
+}
+
+/// Iterator over all the stable matchings of an instance, starting with the one returned by
+/// [`find_stable_marriage`](crate::GaleShapley::find_stable_marriage).
+pub struct StableMarriages {
+    poset: RotationPoset,
+    /// The set of eliminated rotations that gives the last matching returned
+    eliminated: Option<Vec<bool>>,
+}
+
+impl StableMarriages {
+    pub(crate) fn new(poset: RotationPoset) -> Self {
+        StableMarriages {
+            poset,
+            eliminated: None,
+        }
+    }
+}
+
+impl Iterator for StableMarriages {
+    type Item = Matching;
+
+    /// The closed sets of rotations are enumerated in lexicographic order
+    fn next(&mut self) -> Option<Self::Item> {
+        let eliminated = match &mut self.eliminated {
+            None => self.eliminated.insert(vec![false; self.poset.len()]),
+            Some(eliminated) => {
+                if !self.poset.next_closed_set(eliminated) {
+                    return None;
+                }
+                eliminated
+            }
+        };
+        Some(self.poset.matching(eliminated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::random_lists;
+    use crate::*;
+    use rand::{Rng, SeedableRng};
+    use std::collections::BTreeSet;
+
+    /// All the stable matchings, by brute force
+    fn brute_force(men: &[Vec<Woman>], women: &[Vec<Man>]) -> BTreeSet<Vec<(Man, Woman)>> {
+        let rank = |line: &Vec<usize>, x: usize| line.iter().position(|&y| y == x);
+        let prefers = |line: &Vec<usize>, x: usize, partner: Option<usize>| {
+            rank(line, x).is_some_and(|r| partner.is_none_or(|p| r < rank(line, p).unwrap()))
+        };
+        let mut result = BTreeSet::new();
+        let mut stack = vec![vec![]];
+        while let Some(wives) = stack.pop() {
+            let m = wives.len();
+            if m < men.len() {
+                stack.push([&wives[..], &[None]].concat());
+                for (w, line) in women.iter().enumerate() {
+                    if !wives.contains(&Some(w))
+                        && rank(&men[m], w).is_some()
+                        && rank(line, m).is_some()
+                    {
+                        stack.push([&wives[..], &[Some(w)]].concat());
+                    }
+                }
+                continue;
+            }
+            let husband = |w: Woman| wives.iter().position(|&w2| w2 == Some(w));
+            let stable = (0..men.len()).all(|m| {
+                (0..women.len())
+                    .all(|w| !(prefers(&men[m], w, wives[m]) && prefers(&women[w], m, husband(w))))
+            });
+            if stable {
+                let mut pairs: Vec<(Man, Woman)> = wives
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(m, w)| Some((m, (*w)?)))
+                    .collect();
+                pairs.sort_by_key(|&(_, w)| w);
+                result.insert(pairs);
+            }
+        }
+        result
+    }
+
+    fn all_stable(
+        men: Vec<Vec<Woman>>,
+        women: Vec<Vec<Man>>,
+        proposing: Side,
+    ) -> BTreeSet<Vec<(Man, Woman)>> {
+        let mut result = BTreeSet::new();
+        for matching in
+            GaleShapley::init_with_proposing(men, women, proposing).all_stable_marriages()
+        {
+            let pairs = matching.iter().collect();
+            assert!(result.insert(pairs), "a matching was returned twice");
+        }
+        result
+    }
+
+    #[test]
+    fn test_all_stable_marriages() {
+        // each man and each woman has a different first choice, which gives 3 stable matchings
+        let men = vec![vec![0, 1, 2], vec![1, 2, 0], vec![2, 0, 1]];
+        let women = vec![vec![1, 2, 0], vec![2, 0, 1], vec![0, 1, 2]];
+        let all: Vec<Vec<(Man, Woman)>> = GaleShapley::init(men, women)
+            .all_stable_marriages()
+            .map(|matching| matching.iter().collect())
+            .collect();
+        assert_eq!(
+            all,
+            vec![
+                vec![(0, 0), (1, 1), (2, 2)],
+                vec![(2, 0), (0, 1), (1, 2)],
+                vec![(1, 0), (2, 1), (0, 2)],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rotation_poset() {
+        let men = vec![vec![0, 1, 2], vec![1, 2, 0], vec![2, 0, 1]];
+        let women = vec![vec![1, 2, 0], vec![2, 0, 1], vec![0, 1, 2]];
+        let poset = RotationPoset::new(GaleShapley::init(men.clone(), women.clone()));
+        assert_eq!(poset.len(), 2);
+        assert_eq!(poset.rotation(0), vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(poset.rotation(1), vec![(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(poset.edges().collect::<Vec<_>>(), vec![(0, 1)]);
+        assert!(poset.is_closed(&[true, false]));
+        assert!(!poset.is_closed(&[false, true]));
+        let matching = poset.matching(&[true, false]);
+        assert_eq!(matching, Matching::new(3, 3, [(2, 0), (0, 1), (1, 2)]));
+
+        // with women proposing, each woman leaves her husband for the man of the next pair
+        let poset = RotationPoset::new(GaleShapley::init_with_proposing(men, women, Side::Women));
+        assert_eq!(poset.len(), 2);
+        assert_eq!(poset.rotation(0), vec![(1, 0), (2, 1), (0, 2)]);
+        let matching = poset.matching(&[true, true]);
+        assert_eq!(matching, Matching::new(3, 3, [(0, 0), (1, 1), (2, 2)]));
+    }
+
+    #[test]
+    fn test_all_stable_marriages_brute_force() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..300 {
+            let (num_men, num_women) = (rng.gen_range(1..6), rng.gen_range(1..6));
+            let men = random_lists(&mut rng, num_men, num_women, false);
+            let women = random_lists(&mut rng, num_women, num_men, false);
+            let expected = brute_force(&men, &women);
+            assert_eq!(all_stable(men.clone(), women.clone(), Side::Men), expected);
+            assert_eq!(all_stable(men, women, Side::Women), expected);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Deserialization of the solver state, which is checked before it is used.
+//!
+//! The payloads can come from anywhere, so they are first deserialized into raw structs
+//! with the same fields, and only converted when the preferences and the progress are consistent.
+
+use crate::{
+    check_engagements, check_preferences, check_progress, Checkpoint, FreeMen, GaleShapley, Man,
+    Side, Woman, UNACCEPTABLE,
+};
+use serde::Deserialize;
+
+/// A [`GaleShapley`] instance as it is serialized
+#[derive(Deserialize)]
+pub(crate) struct RawGaleShapley {
+    proposing: Side,
+    free_men: FreeMen,
+    men_preferences: Vec<Vec<Woman>>,
+    next_proposal: Vec<usize>,
+    women_preferences: Vec<Vec<usize>>,
+    women_engagement: Vec<Option<Man>>,
+    proposals: usize,
+}
+
+impl TryFrom<RawGaleShapley> for GaleShapley {
+    type Error = String;
+
+    fn try_from(raw: RawGaleShapley) -> Result<GaleShapley, String> {
+        let (num_men, num_women) = (raw.men_preferences.len(), raw.women_preferences.len());
+        check_preferences(&raw.men_preferences, num_women)
+            .map_err(|(m, kind)| format!("invalid preferences for proposer {m}: {kind}"))?;
+        for (w, ranks) in raw.women_preferences.iter().enumerate() {
+            // the ranks of the acceptable men must be 0, 1, 2...
+            let mut listed: Vec<usize> = ranks
+                .iter()
+                .copied()
+                .filter(|&r| r != UNACCEPTABLE)
+                .collect();
+            listed.sort_unstable();
+            if ranks.len() != num_men || listed.iter().enumerate().any(|(i, &r)| i != r) {
+                return Err(format!("invalid ranks for receiver {w}"));
+            }
+        }
+        check_progress(
+            &raw.men_preferences,
+            &raw.women_preferences,
+            &raw.free_men,
+            &raw.next_proposal,
+            &raw.women_engagement,
+        )?;
+        Ok(GaleShapley {
+            proposing: raw.proposing,
+            free_men: raw.free_men,
+            men_preferences: raw.men_preferences,
+            next_proposal: raw.next_proposal,
+            women_preferences: raw.women_preferences,
+            women_engagement: raw.women_engagement,
+            proposals: raw.proposals,
+        })
+    }
+}
+
+/// A [`Checkpoint`] as it is serialized
+#[derive(Deserialize)]
+pub(crate) struct RawCheckpoint {
+    free_men: FreeMen,
+    next_proposal: Vec<usize>,
+    women_engagement: Vec<Option<Man>>,
+    proposals: usize,
+}
+
+impl TryFrom<RawCheckpoint> for Checkpoint {
+    type Error = String;
+
+    /// Only checks what does not depend on the preferences, the rest is checked by [`GaleShapley::restore`]
+    fn try_from(raw: RawCheckpoint) -> Result<Checkpoint, String> {
+        check_engagements(&raw.free_men, &raw.next_proposal, &raw.women_engagement)?;
+        Ok(Checkpoint {
+            free_men: raw.free_men,
+            next_proposal: raw.next_proposal,
+            women_engagement: raw.women_engagement,
+            proposals: raw.proposals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_corrupted_instance() {
+        let mut pb = GaleShapley::init(vec![vec![0, 1], vec![0]], vec![vec![1, 0], vec![0]]);
+        pb.next_engagement_round();
+        let json = serde_json::to_value(&pb).unwrap();
+        assert!(serde_json::from_value::<GaleShapley>(json.clone()).is_ok());
+        let corruptions = [
+            ("next_proposal", serde_json::json!([])),
+            ("men_preferences", serde_json::json!([[0, 2], [0]])),
+            (
+                "women_preferences",
+                serde_json::json!([[1, 1], [0, 18446744073709551615u64]]),
+            ),
+            ("women_engagement", serde_json::json!([null, 0])),
+        ];
+        for (field, value) in corruptions {
+            let mut corrupted = json.clone();
+            corrupted[field] = value;
+            let result = serde_json::from_value::<GaleShapley>(corrupted);
+            assert!(result.is_err(), "{field} was not checked");
+        }
+        // nobody has proposed yet, so the men cannot be missing from the free men
+        let pb = GaleShapley::init(vec![vec![0, 1]; 2], vec![vec![0, 1]; 2]);
+        let mut corrupted = serde_json::to_value(&pb).unwrap();
+        corrupted["free_men"]["men"] = serde_json::json!([]);
+        assert!(serde_json::from_value::<GaleShapley>(corrupted).is_err());
+    }
+
+    #[test]
+    fn test_corrupted_checkpoint() {
+        let pb = GaleShapley::init(vec![vec![0]], vec![vec![0]]);
+        let mut corrupted = serde_json::to_value(pb.checkpoint()).unwrap();
+        corrupted["women_engagement"] = serde_json::json!([0]);
+        assert!(serde_json::from_value::<Checkpoint>(corrupted).is_err());
+
+        // the checkpoint is valid on its own, but the men cannot be missing from the free men before proposing
+        let mut pb = GaleShapley::init(vec![vec![0, 1]; 2], vec![vec![0, 1]; 2]);
+        let mut json = serde_json::to_value(pb.checkpoint()).unwrap();
+        json["free_men"]["men"] = serde_json::json!([]);
+        let checkpoint: Checkpoint = serde_json::from_value(json).unwrap();
+        let restored = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pb.restore(&checkpoint);
+        }));
+        assert!(restored.is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_restore_mismatched_checkpoint() {
+        let mut pb = GaleShapley::init(vec![vec![0]], vec![vec![0]]);
+        let mut json = serde_json::to_value(pb.checkpoint()).unwrap();
+        json["next_proposal"] = serde_json::json!([2]);
+        // the checkpoint is valid on its own, but man 0 cannot have proposed twice in this instance
+        let checkpoint: Checkpoint = serde_json::from_value(json).unwrap();
+        pb.restore(&checkpoint);
+    }
+}
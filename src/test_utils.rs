@@ -0,0 +1,52 @@
+//! Random instances shared by the brute-force tests.
+
+use crate::UNACCEPTABLE;
+use rand::{seq::SliceRandom, Rng};
+
+/// A random list of some of the agents in `0..len`
+pub(crate) fn random_list(rng: &mut impl Rng, len: usize) -> Vec<usize> {
+    let mut line: Vec<usize> = (0..len).collect();
+    line.shuffle(rng);
+    line.truncate(rng.gen_range(0..=len));
+    line
+}
+
+/// `rows` random lists of agents in `0..cols`, which contain all of them if `complete` is set
+pub(crate) fn random_lists(
+    rng: &mut impl Rng,
+    rows: usize,
+    cols: usize,
+    complete: bool,
+) -> Vec<Vec<usize>> {
+    (0..rows)
+        .map(|_| match complete {
+            true => {
+                let mut line: Vec<usize> = (0..cols).collect();
+                line.shuffle(rng);
+                line
+            }
+            false => random_list(rng, cols),
+        })
+        .collect()
+}
+
+/// `rows` random rank rows with ties, where each agent in `0..cols` gets a rank in `0..levels`,
+/// or is unacceptable with probability `1 - acceptable`
+pub(crate) fn random_ranks(
+    rng: &mut impl Rng,
+    rows: usize,
+    cols: usize,
+    levels: usize,
+    acceptable: f64,
+) -> Vec<Vec<usize>> {
+    (0..rows)
+        .map(|_| {
+            (0..cols)
+                .map(|_| match rng.gen_bool(acceptable) {
+                    true => rng.gen_range(0..levels),
+                    false => UNACCEPTABLE,
+                })
+                .collect()
+        })
+        .collect()
+}
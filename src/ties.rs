@@ -0,0 +1,476 @@
+//! Stable marriage with ties, under stability notions stronger than the weak stability
+//! given by [`GaleShapley::init_with_ties`](crate::GaleShapley::init_with_ties).
+//!
+//! The solvers take the same rank matrices as `init_with_ties`:
+//! `men_ranks[m][w]` is the rank of w in m's preferences, `women_ranks[w][m]` the rank of m in w's preferences,
+//! equal ranks mean indifference and [`UNACCEPTABLE`] marks a partner that is not acceptable.
+
+use crate::{Man, Woman, UNACCEPTABLE};
+
+/// Preference lists from which pairs are deleted as the algorithms progress
+struct Lists {
+    men_ranks: Vec<Vec<usize>>,
+    women_ranks: Vec<Vec<usize>>,
+}
+
+impl Lists {
+    fn new(mut men_ranks: Vec<Vec<usize>>, mut women_ranks: Vec<Vec<usize>>) -> Self {
+        let num_men = men_ranks.len();
+        for line in &women_ranks {
+            assert_eq!(
+                line.len(),
+                num_men,
+                "each woman must rank all {num_men} men"
+            );
+        }
+        for (m, line) in men_ranks.iter_mut().enumerate() {
+            assert_eq!(
+                line.len(),
+                women_ranks.len(),
+                "each man must rank all women"
+            );
+            for (w, rank) in line.iter_mut().enumerate() {
+                // a pair is only acceptable if both agents list each other
+                if women_ranks[w][m] == UNACCEPTABLE {
+                    *rank = UNACCEPTABLE;
+                } else if *rank == UNACCEPTABLE {
+                    women_ranks[w][m] = UNACCEPTABLE;
+                }
+            }
+        }
+        Lists {
+            men_ranks,
+            women_ranks,
+        }
+    }
+
+    fn num_men(&self) -> usize {
+        self.men_ranks.len()
+    }
+
+    fn num_women(&self) -> usize {
+        self.women_ranks.len()
+    }
+
+    /// Removes w from m's list and m from w's list
+    fn delete(&mut self, m: Man, w: Woman) {
+        self.men_ranks[m][w] = UNACCEPTABLE;
+        self.women_ranks[w][m] = UNACCEPTABLE;
+    }
+
+    /// The women m likes best among the ones left in his list
+    fn head(&self, m: Man) -> Vec<Woman> {
+        let ranks = &self.men_ranks[m];
+        let best = ranks.iter().copied().min().unwrap_or(UNACCEPTABLE);
+        (0..ranks.len())
+            .filter(|&w| ranks[w] == best && best != UNACCEPTABLE)
+            .collect()
+    }
+
+    /// The men w likes least among the ones left in her list
+    fn tail(&self, w: Woman) -> Vec<Man> {
+        let ranks = &self.women_ranks[w];
+        let worst = ranks.iter().copied().filter(|&r| r != UNACCEPTABLE).max();
+        (0..ranks.len())
+            .filter(|&m| Some(ranks[m]) == worst)
+            .collect()
+    }
+
+    /// The men left in w's list that she likes strictly less than m
+    fn strict_successors(&self, w: Woman, m: Man) -> Vec<Man> {
+        let ranks = &self.women_ranks[w];
+        (0..ranks.len())
+            .filter(|&m2| ranks[m2] > ranks[m] && ranks[m2] != UNACCEPTABLE)
+            .collect()
+    }
+}
+
+/// The engagement relation, in which a man can be engaged to several women and conversely
+struct Engagements {
+    men: Vec<Vec<Woman>>,
+    women: Vec<Vec<Man>>,
+    /// women_ever_engaged[w] is true if w has received at least one proposal
+    women_ever_engaged: Vec<bool>,
+    /// men that may be free with a non-empty list
+    free_men: Vec<Man>,
+}
+
+impl Engagements {
+    fn new(num_men: usize, num_women: usize) -> Self {
+        Engagements {
+            men: vec![vec![]; num_men],
+            women: vec![vec![]; num_women],
+            women_ever_engaged: vec![false; num_women],
+            free_men: (0..num_men).rev().collect(),
+        }
+    }
+
+    fn engage(&mut self, m: Man, w: Woman) {
+        self.men[m].push(w);
+        self.women[w].push(m);
+        self.women_ever_engaged[w] = true;
+    }
+
+    /// Breaks the engagement between m and w if there is one, and deletes the pair from the lists
+    fn delete(&mut self, lists: &mut Lists, m: Man, w: Woman) {
+        lists.delete(m, w);
+        if let Some(i) = self.women[w].iter().position(|&m2| m2 == m) {
+            self.women[w].swap_remove(i);
+            self.men[m].retain(|&w2| w2 != w);
+            if self.men[m].is_empty() {
+                self.free_men.push(m);
+            }
+        }
+    }
+
+    /// Lets every free man propose to all the women at the head of his list.
+    /// Each woman who receives a proposal deletes the men she likes strictly less than the proposer.
+    fn propose(&mut self, lists: &mut Lists) {
+        while let Some(m) = self.free_men.pop() {
+            if !self.men[m].is_empty() {
+                continue;
+            }
+            for w in lists.head(m) {
+                self.engage(m, w);
+                for m2 in lists.strict_successors(w, m) {
+                    self.delete(lists, m2, w);
+                }
+            }
+        }
+    }
+
+    /// Finds a maximum matching in the engagement graph, returned as (wife, husband)
+    fn maximum_matching(&self) -> (Vec<Option<Woman>>, Vec<Option<Man>>) {
+        let mut wife = vec![None; self.men.len()];
+        let mut husband = vec![None; self.women.len()];
+        for m in 0..self.men.len() {
+            let mut visited = vec![false; self.women.len()];
+            self.augment(m, &mut visited, &mut wife, &mut husband);
+        }
+        (wife, husband)
+    }
+
+    /// Looks for an augmenting path starting from m, and applies it if there is one
+    fn augment(
+        &self,
+        m: Man,
+        visited: &mut [bool],
+        wife: &mut [Option<Woman>],
+        husband: &mut [Option<Man>],
+    ) -> bool {
+        for &w in &self.men[m] {
+            if !visited[w] {
+                visited[w] = true;
+                if husband[w].is_none_or(|m2| self.augment(m2, visited, wife, husband)) {
+                    husband[w] = Some(m);
+                    wife[m] = Some(w);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// The women adjacent to the critical set of men in the engagement graph:
+    /// the men reachable by alternating paths from the men left unmatched by a maximum matching.
+    /// This set of men Z maximizes the deficiency |Z| - |N(Z)|.
+    fn critical_set_neighbours(
+        &self,
+        wife: &[Option<Woman>],
+        husband: &[Option<Man>],
+    ) -> Vec<Woman> {
+        let mut stack: Vec<Man> = (0..self.men.len())
+            .filter(|&m| wife[m].is_none() && !self.men[m].is_empty())
+            .collect();
+        let mut in_critical_set = vec![false; self.men.len()];
+        let mut neighbours = vec![false; self.women.len()];
+        while let Some(m) = stack.pop() {
+            in_critical_set[m] = true;
+            for &w in &self.men[m] {
+                if !neighbours[w] {
+                    neighbours[w] = true;
+                    let m2 = husband[w].expect("internal error: the matching is not maximum");
+                    if !in_critical_set[m2] {
+                        stack.push(m2);
+                    }
+                }
+            }
+        }
+        (0..self.women.len()).filter(|&w| neighbours[w]).collect()
+    }
+
+    /// The engagement relation as a matching, if it is one in which every woman who was ever engaged is matched
+    fn into_matching(self) -> Option<Vec<(Man, Woman)>> {
+        if self.men.iter().any(|ws| ws.len() > 1) {
+            return None;
+        }
+        let mut pairs = Vec::new();
+        for (w, men) in self.women.iter().enumerate() {
+            match men[..] {
+                [m] => pairs.push((m, w)),
+                [] if !self.women_ever_engaged[w] => {}
+                _ => return None,
+            }
+        }
+        Some(pairs)
+    }
+}
+
+/// Finds a super-stable matching: one with no man and woman who both like each other
+/// at least as much as their partners. Such a matching does not always exist, in that case this returns None.
+///
+/// This is Irving's algorithm for ties, extended to incomplete lists by Manlove.
+/// When it exists, the super-stable matching is returned as `(Man, Woman)` pairs in the order of the women.
+pub fn super_stable_marriage(
+    men_ranks: Vec<Vec<usize>>,
+    women_ranks: Vec<Vec<usize>>,
+) -> Option<Vec<(Man, Woman)>> {
+    let mut lists = Lists::new(men_ranks, women_ranks);
+    let mut engagements = Engagements::new(lists.num_men(), lists.num_women());
+    loop {
+        engagements.propose(&mut lists);
+        let multiply_engaged: Vec<Woman> = (0..lists.num_women())
+            .filter(|&w| engagements.women[w].len() > 1)
+            .collect();
+        if multiply_engaged.is_empty() {
+            break;
+        }
+        for w in multiply_engaged {
+            for m in lists.tail(w) {
+                engagements.delete(&mut lists, m, w);
+            }
+        }
+    }
+    engagements.into_matching()
+}
+
+/// Finds a strongly stable matching: one with no man and woman who would both like each other
+/// at least as much as their partners, with at least one of them strictly preferring the other.
+/// Such a matching does not always exist, in that case this returns None.
+///
+/// This is Irving's algorithm for ties, extended to incomplete lists by Manlove.
+/// When it exists, the strongly stable matching is returned as `(Man, Woman)` pairs in the order of the women.
+pub fn strongly_stable_marriage(
+    men_ranks: Vec<Vec<usize>>,
+    women_ranks: Vec<Vec<usize>>,
+) -> Option<Vec<(Man, Woman)>> {
+    let mut lists = Lists::new(men_ranks, women_ranks);
+    let mut engagements = Engagements::new(lists.num_men(), lists.num_women());
+    loop {
+        engagements.propose(&mut lists);
+        let (wife, husband) = engagements.maximum_matching();
+        let critical_women = engagements.critical_set_neighbours(&wife, &husband);
+        if critical_women.is_empty() {
+            // every engaged man is matched. The engagement graph has a matching that is strongly stable
+            // if and only if this one also matches every woman who was ever engaged.
+            let mut pairs = Vec::new();
+            for (w, m) in husband.into_iter().enumerate() {
+                match m {
+                    Some(m) => pairs.push((m, w)),
+                    None if engagements.women_ever_engaged[w] => return None,
+                    None => {}
+                }
+            }
+            return Some(pairs);
+        }
+        for w in critical_women {
+            for m in lists.tail(w) {
+                engagements.delete(&mut lists, m, w);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::random_ranks;
+    use rand::{Rng, SeedableRng};
+
+    /// Whether a prefers b over c, where None means being single
+    fn prefers(ranks: &[usize], b: usize, c: Option<usize>) -> bool {
+        c.is_none_or(|c| ranks[b] < ranks[c])
+    }
+
+    /// Whether a likes b at least as much as c, where None means being single
+    fn likes_as_much(ranks: &[usize], b: usize, c: Option<usize>) -> bool {
+        c.is_none_or(|c| ranks[b] <= ranks[c])
+    }
+
+    /// All the matchings of mutually acceptable pairs, as wife[m]
+    fn all_matchings(
+        men_ranks: &[Vec<usize>],
+        women_ranks: &[Vec<usize>],
+    ) -> Vec<Vec<Option<Woman>>> {
+        fn extend(
+            m: Man,
+            wife: &mut Vec<Option<Woman>>,
+            taken: &mut Vec<bool>,
+            acceptable: &dyn Fn(Man, Woman) -> bool,
+            out: &mut Vec<Vec<Option<Woman>>>,
+        ) {
+            if m == wife.len() {
+                out.push(wife.clone());
+                return;
+            }
+            extend(m + 1, wife, taken, acceptable, out);
+            for w in 0..taken.len() {
+                if !taken[w] && acceptable(m, w) {
+                    taken[w] = true;
+                    wife[m] = Some(w);
+                    extend(m + 1, wife, taken, acceptable, out);
+                    wife[m] = None;
+                    taken[w] = false;
+                }
+            }
+        }
+        let acceptable =
+            |m: Man, w: Woman| men_ranks[m][w] != UNACCEPTABLE && women_ranks[w][m] != UNACCEPTABLE;
+        let mut out = vec![];
+        extend(
+            0,
+            &mut vec![None; men_ranks.len()],
+            &mut vec![false; women_ranks.len()],
+            &acceptable,
+            &mut out,
+        );
+        out
+    }
+
+    /// Whether (m, w) blocks the matching, given how each side has to compare the pair with their partners
+    fn blocking_pairs(
+        men_ranks: &[Vec<usize>],
+        women_ranks: &[Vec<usize>],
+        wife: &[Option<Woman>],
+        blocks: impl Fn(bool, bool, bool, bool) -> bool,
+    ) -> usize {
+        let mut husband = vec![None; women_ranks.len()];
+        for (m, w) in wife.iter().enumerate() {
+            if let Some(w) = *w {
+                husband[w] = Some(m);
+            }
+        }
+        let mut count = 0;
+        for m in 0..men_ranks.len() {
+            for w in 0..women_ranks.len() {
+                if wife[m] == Some(w)
+                    || men_ranks[m][w] == UNACCEPTABLE
+                    || women_ranks[w][m] == UNACCEPTABLE
+                {
+                    continue;
+                }
+                let (mr, wr) = (&men_ranks[m], &women_ranks[w]);
+                if blocks(
+                    prefers(mr, w, wife[m]),
+                    likes_as_much(mr, w, wife[m]),
+                    prefers(wr, m, husband[w]),
+                    likes_as_much(wr, m, husband[w]),
+                ) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    fn to_wife(num_men: usize, pairs: &[(Man, Woman)]) -> Vec<Option<Woman>> {
+        let mut wife = vec![None; num_men];
+        for &(m, w) in pairs {
+            assert_eq!(wife[m], None);
+            wife[m] = Some(w);
+        }
+        wife
+    }
+
+    #[test]
+    fn test_super_stable_exists() {
+        // man 0 is indifferent, but woman 1 prefers him while woman 0 prefers man 1
+        let men_ranks = vec![vec![0, 0], vec![0, 1]];
+        let women_ranks = vec![vec![1, 0], vec![0, 1]];
+        assert_eq!(
+            super_stable_marriage(men_ranks, women_ranks),
+            Some(vec![(1, 0), (0, 1)])
+        );
+    }
+
+    #[test]
+    fn test_super_stable_does_not_exist() {
+        // everybody is indifferent, so any pair left unmatched together blocks
+        let ranks = || vec![vec![0, 0], vec![0, 0]];
+        assert_eq!(super_stable_marriage(ranks(), ranks()), None);
+    }
+
+    #[test]
+    fn test_strongly_stable_exists() {
+        // nobody strictly prefers anybody, so any complete matching is strongly stable
+        let ranks = || vec![vec![0, 0], vec![0, 0]];
+        assert_eq!(
+            strongly_stable_marriage(ranks(), ranks()),
+            Some(vec![(1, 0), (0, 1)])
+        );
+    }
+
+    #[test]
+    fn test_strongly_stable_does_not_exist() {
+        // both men want the only woman, who is indifferent between them
+        let men_ranks = vec![vec![0], vec![0]];
+        let women_ranks = vec![vec![0, 0]];
+        assert_eq!(strongly_stable_marriage(men_ranks, women_ranks), None);
+    }
+
+    #[test]
+    fn test_strongly_stable_brute_force() {
+        let strong_blocks = |m_strict: bool, m_weak: bool, w_strict: bool, w_weak: bool| {
+            (m_strict && w_weak) || (m_weak && w_strict)
+        };
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        for _ in 0..500 {
+            let (num_men, num_women) = (rng.gen_range(1..5), rng.gen_range(1..5));
+            let men_ranks = random_ranks(&mut rng, num_men, num_women, 4, 0.8);
+            let women_ranks = random_ranks(&mut rng, num_women, num_men, 4, 0.8);
+            let lists = Lists::new(men_ranks.clone(), women_ranks.clone());
+            let exists = all_matchings(&lists.men_ranks, &lists.women_ranks)
+                .iter()
+                .any(|wife| {
+                    blocking_pairs(&lists.men_ranks, &lists.women_ranks, wife, strong_blocks) == 0
+                });
+            match strongly_stable_marriage(men_ranks, women_ranks) {
+                Some(pairs) => {
+                    let wife = to_wife(num_men, &pairs);
+                    assert_eq!(
+                        blocking_pairs(&lists.men_ranks, &lists.women_ranks, &wife, strong_blocks),
+                        0
+                    );
+                }
+                None => assert!(!exists),
+            }
+        }
+    }
+
+    #[test]
+    fn test_super_stable_brute_force() {
+        let super_blocks = |_: bool, m_weak: bool, _: bool, w_weak: bool| m_weak && w_weak;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        for _ in 0..500 {
+            let (num_men, num_women) = (rng.gen_range(1..5), rng.gen_range(1..5));
+            let men_ranks = random_ranks(&mut rng, num_men, num_women, 4, 0.8);
+            let women_ranks = random_ranks(&mut rng, num_women, num_men, 4, 0.8);
+            let lists = Lists::new(men_ranks.clone(), women_ranks.clone());
+            let exists = all_matchings(&lists.men_ranks, &lists.women_ranks)
+                .iter()
+                .any(|wife| {
+                    blocking_pairs(&lists.men_ranks, &lists.women_ranks, wife, super_blocks) == 0
+                });
+            match super_stable_marriage(men_ranks, women_ranks) {
+                Some(pairs) => {
+                    let wife = to_wife(num_men, &pairs);
+                    assert_eq!(
+                        blocking_pairs(&lists.men_ranks, &lists.women_ranks, &wife, super_blocks),
+                        0
+                    );
+                }
+                None => assert!(!exists),
+            }
+        }
+    }
+}
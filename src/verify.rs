@@ -0,0 +1,158 @@
+//! Checks matchings that come from elsewhere against the preferences.
+
+use crate::{check_instance, make_partial_rank_matrix, Man, PreferenceError, Woman, UNACCEPTABLE};
+
+/// Something wrong with the pairs of a matching, independently of its stability
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MatchingIssue {
+    /// The pair refers to a man that does not exist
+    UnknownMan(Man, Woman),
+    /// The pair refers to a woman that does not exist
+    UnknownWoman(Man, Woman),
+    /// The pair is ignored because the man is already matched by an earlier pair
+    ManMatchedTwice(Man, Woman),
+    /// The pair is ignored because the woman is already matched by an earlier pair
+    WomanMatchedTwice(Man, Woman),
+    /// The man or the woman does not list the other one
+    UnacceptablePair(Man, Woman),
+}
+
+/// What [`verify_matching`] found in a matching
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchingReport {
+    /// Men and women who both prefer each other to their partners, or who are single and find each other acceptable
+    pub blocking_pairs: Vec<(Man, Woman)>,
+    /// Problems with the pairs themselves, in the order of the pairs
+    pub issues: Vec<MatchingIssue>,
+    /// Men that are not in any pair
+    pub unmatched_men: Vec<Man>,
+    /// Women that are not in any pair
+    pub unmatched_women: Vec<Woman>,
+}
+
+impl MatchingReport {
+    /// Whether the matching is valid and has no blocking pair
+    pub fn is_stable(&self) -> bool {
+        self.blocking_pairs.is_empty() && self.issues.is_empty()
+    }
+}
+
+/// Checks a matching against preferences given as in [`GaleShapley::init`](crate::GaleShapley::init).
+/// The blocking pairs are computed from the valid pairs of the matching, listed by man then by woman.
+///
+/// Returns an error if a preference list contains an agent that does not exist, or the same agent twice.
+pub fn verify_matching(
+    men_preferences: &[Vec<Woman>],
+    women_preferences: &[Vec<Man>],
+    matching: impl IntoIterator<Item = (Man, Woman)>,
+) -> Result<MatchingReport, PreferenceError> {
+    check_instance(men_preferences, women_preferences)?;
+    let (num_men, num_women) = (men_preferences.len(), women_preferences.len());
+    let men_ranks = make_partial_rank_matrix(men_preferences.to_vec(), num_women);
+    let women_ranks = make_partial_rank_matrix(women_preferences.to_vec(), num_men);
+    let mut wives = vec![None; num_men];
+    let mut husbands = vec![None; num_women];
+    let mut report = MatchingReport::default();
+
+    for (m, w) in matching {
+        let issue = if m >= num_men {
+            MatchingIssue::UnknownMan(m, w)
+        } else if w >= num_women {
+            MatchingIssue::UnknownWoman(m, w)
+        } else if wives[m].is_some() {
+            MatchingIssue::ManMatchedTwice(m, w)
+        } else if husbands[w].is_some() {
+            MatchingIssue::WomanMatchedTwice(m, w)
+        } else {
+            wives[m] = Some(w);
+            husbands[w] = Some(m);
+            if men_ranks[m][w] != UNACCEPTABLE && women_ranks[w][m] != UNACCEPTABLE {
+                continue;
+            }
+            MatchingIssue::UnacceptablePair(m, w)
+        };
+        report.issues.push(issue);
+    }
+
+    // whether the agent with these ranks prefers x to their partner, an unacceptable partner being the worst
+    let prefers = |ranks: &[usize], x: usize, partner: Option<usize>| {
+        ranks[x] != UNACCEPTABLE && partner.is_none_or(|p| ranks[x] < ranks[p])
+    };
+    for m in 0..num_men {
+        for w in 0..num_women {
+            if prefers(&men_ranks[m], w, wives[m]) && prefers(&women_ranks[w], m, husbands[w]) {
+                report.blocking_pairs.push((m, w));
+            }
+        }
+    }
+    report.unmatched_men = (0..num_men).filter(|&m| wives[m].is_none()).collect();
+    report.unmatched_women = (0..num_women).filter(|&w| husbands[w].is_none()).collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GaleShapley, PreferenceErrorKind, Side};
+
+    #[test]
+    fn test_verify_stable_matching() {
+        let men_preferences = vec![vec![0, 1, 2], vec![2, 1, 0], vec![1, 2, 0]];
+        let women_preferences = vec![vec![0, 2, 1], vec![2, 1, 0], vec![2, 0, 1]];
+        let matching = GaleShapley::init(men_preferences.clone(), women_preferences.clone())
+            .find_stable_marriage();
+        let report = verify_matching(&men_preferences, &women_preferences, &matching).unwrap();
+        assert!(report.is_stable());
+        assert_eq!(report, MatchingReport::default());
+    }
+
+    #[test]
+    fn test_verify_blocking_pairs() {
+        let men_preferences = vec![vec![0, 1], vec![0, 1]];
+        let women_preferences = vec![vec![1, 0], vec![1, 0]];
+        // both man 1 and woman 0 prefer each other to their partners
+        let report =
+            verify_matching(&men_preferences, &women_preferences, [(0, 0), (1, 1)]).unwrap();
+        assert_eq!(report.blocking_pairs, vec![(1, 0)]);
+        assert!(report.issues.is_empty());
+        // single agents block with anybody who accepts them and prefers them to their partner
+        let report = verify_matching(&men_preferences, &women_preferences, [(0, 0)]).unwrap();
+        assert_eq!(report.blocking_pairs, vec![(1, 0), (1, 1)]);
+        assert_eq!(report.unmatched_men, vec![1]);
+        assert_eq!(report.unmatched_women, vec![1]);
+    }
+
+    #[test]
+    fn test_verify_issues() {
+        let men_preferences = vec![vec![0], vec![0, 1]];
+        let women_preferences = vec![vec![0, 1], vec![1]];
+        let matching = [(0, 1), (1, 1), (2, 0), (1, 0), (0, 3)];
+        let report = verify_matching(&men_preferences, &women_preferences, matching).unwrap();
+        assert_eq!(
+            report.issues,
+            vec![
+                MatchingIssue::UnacceptablePair(0, 1),
+                MatchingIssue::WomanMatchedTwice(1, 1),
+                MatchingIssue::UnknownMan(2, 0),
+                MatchingIssue::UnknownWoman(0, 3),
+            ]
+        );
+        // man 0 is with a woman he does not accept, so he prefers woman 0, who prefers him to man 1
+        assert_eq!(report.blocking_pairs, vec![(0, 0)]);
+        assert!(!report.is_stable());
+    }
+
+    #[test]
+    fn test_verify_invalid_preferences() {
+        let error = verify_matching(&[vec![5]], &[vec![0]], [(0, 0)]).unwrap_err();
+        assert_eq!(
+            error.kind,
+            PreferenceErrorKind::OutOfRange { agent: 5, len: 1 }
+        );
+        let error = verify_matching(&[vec![0]], &[vec![0, 0]], [(0, 0)]).unwrap_err();
+        assert_eq!(error.side, Side::Women);
+        assert_eq!(error.kind, PreferenceErrorKind::Duplicate(0));
+    }
+}